@@ -1,16 +1,19 @@
 use serde::{Deserialize, Serialize};
 use std::sync::Mutex;
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use crate::commands;
 use crate::context;
-use crate::settings::SettingsState;
-use crate::session_manager::{SessionState, Session, save_session_to_disk};
+use crate::settings::{AppSettings, SettingsState};
+use crate::session_manager::{SessionState, Session, DEFAULT_TITLE, has_placeholder_title, load_session_metadata, save_session_to_disk, update_session};
 use crate::audit;
 use reqwest::Client;
 use serde_json::{json, Value};
-use tauri::Emitter;
+use tauri::{Emitter, Manager};
 use std::time::{SystemTime, UNIX_EPOCH, Duration};
-use crate::logging;
+use crate::logging::{self, SESSION_TARGET};
+use tracing::Instrument;
+use crate::redact;
 
 // Re-export Message structs so other modules can use them
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -58,23 +61,23 @@ pub struct FunctionCall {
     pub arguments: String,
 }
 
-#[derive(Serialize)]
-struct OpenAIChatRequest {
-    model: String,
-    messages: Vec<Message>,
-    tools: Vec<Value>,
-    tool_choice: String,
-}
-
 #[derive(Deserialize)]
 struct OpenAIChatResponse {
     choices: Vec<OpenAIChoice>,
+    #[serde(default)]
+    usage: Option<OpenAIUsage>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIUsage {
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    total_tokens: u64,
 }
 
 #[derive(Deserialize)]
 struct OpenAIChoice {
     message: Message,
-    finish_reason: Option<String>,
 }
 
 #[derive(Serialize, Clone)]
@@ -99,20 +102,8 @@ struct TelemetryEvent {
     kind: String,
 }
 
-fn is_sensitive_tool(function_name: &str) -> bool {
-    matches!(
-        function_name,
-        "execute_command"
-            | "write_file"
-            | "open_app"
-            | "create_docx"
-            | "create_slide_deck"
-            | "search_web"
-    )
-}
-
 const APPROVAL_EXPIRY_SECS: u64 = 600; // 10 minutes
-const SAFE_COMMANDS: &[&str] = &["ls", "dir", "pwd", "cat", "type", "echo"];
+const APPROVAL_QUEUE_FILE: &str = "pending_approvals.json";
 
 fn now_ts() -> u64 {
     SystemTime::now()
@@ -121,156 +112,517 @@ fn now_ts() -> u64 {
         .as_secs()
 }
 
-fn sanitize_history_for_storage(history: &[Message]) -> Vec<Message> {
-    history
-        .iter()
-        .map(|msg| {
-            let mut clone = msg.clone();
-            if clone.role == "tool" {
-                clone.content = Some(MessageContent::Text("Tool output omitted for privacy.".into()));
-            } else if let Some(MessageContent::Text(text)) = &clone.content {
-                if text.contains("data:image") {
-                    clone.content = Some(MessageContent::Text("Image data redacted.".into()));
-                }
-            } else if let Some(MessageContent::Parts(_)) = &clone.content {
-                clone.content = Some(MessageContent::Text("Structured content redacted.".into()));
-            }
-            clone.tool_calls = None;
-            clone.tool_call_id = None;
-            clone
-        })
-        .collect()
+const HIGH_RISK_COMMANDS: &[&str] = &[
+    "rm", "del", "rmdir", "rd", "format", "mkfs", "dd", "shutdown", "reboot", "sudo", "chmod", "chown",
+    "reg", "curl", "wget", "invoke-webrequest", "iwr", "git",
+];
+const PREVIEW_MAX_CHARS: usize = 20_000;
+
+// One-line summary shown in chat, e.g. "Would run: cargo build"
+fn describe_call(function_name: &str, args: &Value) -> String {
+    match function_name {
+        "execute_command" => {
+            let cmd = args["command"].as_str().unwrap_or("");
+            let args_vec: Vec<String> = args["args"].as_array().map(|arr| arr.iter().map(|v| v.as_str().unwrap_or("").to_string()).collect()).unwrap_or_default();
+            format!("Would run: {} {}", cmd, args_vec.join(" "))
+        }
+        "write_file" => {
+            let path = args["path"].as_str().unwrap_or("");
+            format!("Would write to {}", path)
+        }
+        _ => format!("Would run {}", function_name),
+    }
 }
 
-fn path_out_of_scope(working_dir: &Option<String>, target: &str) -> bool {
-    if let Some(base) = working_dir {
-        if base.is_empty() {
-            return false;
+// Unified diff for writes, full command line for exec, pretty args otherwise.
+fn render_preview(function_name: &str, args: &Value, working_dir: &Option<String>) -> String {
+    let preview = match function_name {
+        "write_file" => {
+            let path = args["path"].as_str().unwrap_or("");
+            let new_content = args["content"].as_str().unwrap_or("");
+            // Nothing outside the workspace is read before the user has approved the call
+            let old_content = if policy::path_out_of_scope(working_dir, path) {
+                String::new()
+            } else {
+                std::fs::read_to_string(policy::resolve_path(working_dir, path)).unwrap_or_default()
+            };
+            similar::TextDiff::from_lines(old_content.as_str(), new_content)
+                .unified_diff()
+                .context_radius(3)
+                .header(&format!("a/{}", path), &format!("b/{}", path))
+                .to_string()
         }
-        let base_path = std::path::Path::new(base);
-        let target_path = std::path::Path::new(target);
-        if let (Ok(base_canon), Ok(target_canon)) = (base_path.canonicalize(), target_path.canonicalize()) {
-            return !target_canon.starts_with(&base_canon)
-        } else {
-            false
+        "execute_command" => {
+            let cmd = args["command"].as_str().unwrap_or("");
+            let args_vec: Vec<String> = args["args"].as_array().map(|arr| arr.iter().map(|v| v.as_str().unwrap_or("").to_string()).collect()).unwrap_or_default();
+            let cwd = working_dir.clone().unwrap_or_else(|| ".".to_string());
+            format!("$ cd {}\n$ {} {}", cwd, cmd, args_vec.join(" "))
         }
+        _ => serde_json::to_string_pretty(args).unwrap_or_default(),
+    };
+    if preview.chars().count() > PREVIEW_MAX_CHARS {
+        let truncated: String = preview.chars().take(PREVIEW_MAX_CHARS).collect();
+        format!("{}\n... (truncated)", truncated)
     } else {
-        false
+        preview
+    }
+}
+
+fn assess_risk(function_name: &str, args: &Value, working_dir: &Option<String>) -> &'static str {
+    match function_name {
+        "execute_command" => {
+            let cmd = args["command"].as_str().unwrap_or("").to_lowercase();
+            let line = format!("{} {}", cmd, args["args"]);
+            if HIGH_RISK_COMMANDS.contains(&cmd.as_str()) || line.contains('|') || line.contains("&&") || line.contains('>') {
+                "high"
+            } else {
+                "medium"
+            }
+        }
+        "write_file" | "read_file" | "open_app" => {
+            let path = args["path"].as_str().unwrap_or("");
+            if policy::path_out_of_scope(working_dir, path) || path.to_lowercase().starts_with("http") {
+                "high"
+            } else if function_name == "read_file" {
+                "low"
+            } else {
+                "medium"
+            }
+        }
+        "create_docx" | "create_slide_deck" | "search_web" => "medium",
+        _ => "low",
     }
 }
 
 fn request_approval(
-    approval_state: &ApprovalState,
-    app: &tauri::AppHandle,
+    ctx: &TurnContext<'_>,
     function_name: &str,
-    action: String,
     args: Value,
-    working_dir: Option<String>,
     reason: String,
-    session_id: Option<String>,
-) -> String {
-    let id = uuid::Uuid::new_v4().to_string();
-    let expires_at = now_ts() + APPROVAL_EXPIRY_SECS;
+    tool_call_id: &str,
+) -> (String, String) {
+    let (approval_state, app) = (ctx.approval_state, ctx.app);
+    let working_dir = ctx.working_dir.clone();
+    let pending = PendingApproval {
+        id: uuid::Uuid::new_v4().to_string(),
+        function_name: function_name.to_string(),
+        action: describe_call(function_name, &args),
+        preview: render_preview(function_name, &args, &working_dir),
+        risk: assess_risk(function_name, &args, &working_dir).to_string(),
+        reason: reason.clone(),
+        args,
+        working_dir,
+        expires_at: now_ts() + APPROVAL_EXPIRY_SECS,
+        session_id: ctx.session_id.clone(),
+        tool_call_id: Some(tool_call_id.to_string()),
+        args_redacted: false,
+    };
+    let id = pending.id.clone();
     {
         let mut queue = approval_state.queue.lock().unwrap_or_else(|e| e.into_inner());
-        queue.push(PendingApproval {
-            id: id.clone(),
-            function_name: function_name.to_string(),
-            action: action.clone(),
-            args: args.clone(),
-            working_dir: working_dir.clone(),
-            expires_at,
-            session_id,
-        });
+        queue.push(pending.clone());
+        persist_queue(approval_state, &queue);
     }
 
-    let _ = app.emit("approval_request", json!({
-        "id": id,
-        "action": action,
-        "reason": reason,
-        "expires_at": expires_at
-    }));
+    let _ = app.emit("approval_request", &pending);
 
-    format!("Approval required ({function_name}). Reply 'approve {id}' or 'deny {id}'. Reason: {reason}")
+    let notice = format!("Approval required ({function_name}). Reply 'approve {id}' or 'deny {id}'. Reason: {reason}");
+    (id, notice)
 }
 
-fn pop_approval(approval_state: &ApprovalState, id: &str) -> Option<PendingApproval> {
-    let mut queue = approval_state.queue.lock().ok()?;
+// Drops expired approvals (and the turns waiting on them) and tells the UI.
+pub fn prune_expired_approvals(app: &tauri::AppHandle, approval_state: &ApprovalState) -> Vec<PendingApproval> {
     let now = now_ts();
-    let mut idx = None;
-    for (i, item) in queue.iter().enumerate() {
-        if item.expires_at <= now {
+    let expired: Vec<PendingApproval> = {
+        let mut queue = approval_state.queue.lock().unwrap_or_else(|e| e.into_inner());
+        let (expired, live): (Vec<_>, Vec<_>) = queue.drain(..).partition(|item| item.expires_at <= now);
+        *queue = live;
+        if !expired.is_empty() {
+            persist_queue(approval_state, &queue);
+        }
+        expired
+    };
+    if !expired.is_empty() {
+        let mut suspended = approval_state.suspended.lock().unwrap_or_else(|e| e.into_inner());
+        for item in &expired {
+            suspended.remove(&item.id);
+            let _ = app.emit("approval_expired", json!({"id": item.id, "session_id": item.session_id}));
+        }
+        drop(suspended);
+        note_expired_in_sessions(&expired, "expired before it was approved");
+    }
+    expired
+}
+
+// Secrets in previews and args stay in memory only. An approval whose args had to be redacted
+// cannot be run after a restart (see `run_approved`). Written to a temp file and renamed so a
+// crash mid-write cannot leave a truncated queue.
+fn persist_queue(approval_state: &ApprovalState, queue: &[PendingApproval]) {
+    let path_guard = approval_state.path.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(path) = path_guard.as_ref().filter(|path| crate::schema::ensure_writable(path).is_ok()) else {
+        return;
+    };
+    let saved: Vec<PendingApproval> = queue
+        .iter()
+        .map(|item| {
+            let args = redact::redact_value(&item.args);
+            PendingApproval {
+                args_redacted: item.args_redacted || args != item.args,
+                args,
+                preview: redact::redact(&item.preview),
+                ..item.clone()
+            }
+        })
+        .collect();
+    if let Ok(json) = serde_json::to_string_pretty(&saved) {
+        let tmp_path = path.with_extension("json.tmp");
+        if std::fs::write(&tmp_path, json).is_ok() {
+            let _ = std::fs::rename(&tmp_path, path);
+        }
+    }
+}
+
+// Gives every tool call in `messages` that has no `tool` reply one, inserted after the replies
+// that follow its assistant message; the provider rejects histories with unanswered calls.
+// `answer` is the reply for one call in particular, the rest get `fallback`.
+fn answer_open_tool_calls(messages: &mut Vec<Message>, answer: Option<(&str, &str)>, fallback: &str) {
+    let mut i = 0;
+    while i < messages.len() {
+        let Some(calls) = messages[i].tool_calls.clone().filter(|calls| !calls.is_empty()) else {
+            i += 1;
             continue;
+        };
+        let mut end = i + 1;
+        while end < messages.len() && messages[end].role == "tool" {
+            end += 1;
         }
-        if item.id == id {
-            idx = Some(i);
-            break;
+        let answered: Vec<String> = messages[i + 1..end].iter().filter_map(|m| m.tool_call_id.clone()).collect();
+        for call in calls.iter().filter(|call| !answered.contains(&call.id)) {
+            let text = match answer {
+                Some((id, text)) if id == call.id => text,
+                _ => fallback,
+            };
+            messages.insert(end, tool_reply(&call.id, MessageContent::Text(text.to_string())));
+            end += 1;
         }
+        i = end;
     }
-    if let Some(i) = idx {
-        Some(queue.remove(i))
-    } else {
-        None
+}
+
+// Settles an approval outside of its turn: the waiting tool call gets `reply` so the stored
+// history stays valid, and `note` tells the user what happened.
+fn close_out_approval(item: &PendingApproval, reply: &str, note: String) {
+    let Some(sid) = &item.session_id else {
+        return;
+    };
+    let fallback = format!("Not executed: the turn stopped at approval {}.", item.id);
+    let _ = update_session(sid, |session| {
+        answer_open_tool_calls(&mut session.messages, item.tool_call_id.as_deref().map(|id| (id, reply)), &fallback);
+        session.messages.push(Message {
+            role: "assistant".into(),
+            content: Some(MessageContent::Text(note)),
+            tool_calls: None,
+            tool_call_id: None,
+        });
+        session.updated_at = now_ts();
+    });
+}
+
+// Leaves a visible marker in the session so the earlier "Approval required" message isn't left dangling.
+fn note_expired_in_sessions(expired: &[PendingApproval], why: &str) {
+    for item in expired {
+        let reply = format!("Not executed: the approval {}.", why);
+        let note = format!("Approval {} ({}) {}. {} was not run.", item.id, item.function_name, why, item.action);
+        close_out_approval(item, &reply, note);
     }
 }
 
-fn approval_reason(
-    function_name: &str,
-    args: &Value,
-    working_dir: &Option<String>,
-    settings: &crate::settings::AppSettings,
-) -> Option<String> {
-    if settings.read_only && is_sensitive_tool(function_name) {
-        return Some("Read-only mode is enabled".to_string());
+// Reloads approvals saved by a previous run. Live ones go back in the queue (the
+// resumable turn is gone, so approving runs the call on its own); expired ones
+// are closed out in their session history.
+pub fn init_approvals(app_handle: &tauri::AppHandle) -> ApprovalState {
+    let data_dir = app_handle.path().app_data_dir().unwrap_or_default();
+    if !data_dir.exists() {
+        let _ = std::fs::create_dir_all(&data_dir);
     }
+    let path = data_dir.join(APPROVAL_QUEUE_FILE);
+    let saved: Vec<PendingApproval> =
+        crate::schema::load_file(&path, |value| serde_json::from_value(value).map_err(|e| e.to_string()))
+            .unwrap_or_default();
 
-    match function_name {
-        "write_file" | "read_file" => {
-            let path = args["path"].as_str().unwrap_or("");
-            if path_out_of_scope(working_dir, path) {
-                return Some("Path is outside the active workspace".to_string());
-            }
+    let now = now_ts();
+    let (expired, live): (Vec<_>, Vec<_>) = saved.into_iter().partition(|item| item.expires_at <= now);
+    note_expired_in_sessions(&expired, "expired while DeskWork was closed");
+
+    let state = ApprovalState {
+        path: Mutex::new(Some(path)),
+        ..Default::default()
+    };
+    persist_queue(&state, &live);
+    *state.queue.lock().unwrap_or_else(|e| e.into_inner()) = live;
+    state
+}
+
+// Background sweep so the UI hears about expiries even when nobody is chatting.
+pub fn spawn_approval_reaper(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            let approval_state = app.state::<ApprovalState>();
+            prune_expired_approvals(&app, &approval_state);
         }
+    });
+}
+
+// Arg patterns for "remember this decision": the same program, or the same path. Remembered
+// approvals of commands are narrowed further to the exact arguments (see `resolve_approval`).
+fn remembered_patterns(function_name: &str, args: &Value) -> HashMap<String, String> {
+    let mut patterns = HashMap::new();
+    match function_name {
         "execute_command" => {
-            let cmd = args["command"].as_str().unwrap_or("").to_lowercase();
-            if !SAFE_COMMANDS.contains(&cmd.as_str()) {
-                return Some(format!("Command '{}' is not in the allowlist", cmd));
-            }
+            patterns.insert("command".to_string(), args["command"].as_str().unwrap_or("").to_string());
         }
-        "open_app" => {
-            let path = args["path"].as_str().unwrap_or("");
-            if path.to_lowercase().starts_with("http") {
-                return Some("External URL requires approval".to_string());
-            }
-            if path_out_of_scope(working_dir, path) {
-                return Some("Application path is outside the active workspace".to_string());
+        _ => {
+            if let Some(path) = args["path"].as_str() {
+                patterns.insert("path".to_string(), path.to_string());
             }
         }
-        _ => {}
     }
+    patterns
+}
+
+// Drops what a deleted session could resume from: pending approvals, suspended turns and
+// session-scoped grants.
+pub fn forget_session(approval_state: &ApprovalState, session_id: &str) {
+    let mut queue = approval_state.queue.lock().unwrap_or_else(|e| e.into_inner());
+    queue.retain(|item| item.session_id.as_deref() != Some(session_id));
+    persist_queue(approval_state, &queue);
+    drop(queue);
+    let mut suspended = approval_state.suspended.lock().unwrap_or_else(|e| e.into_inner());
+    suspended.retain(|_, turn| turn.session_id.as_deref() != Some(session_id));
+    drop(suspended);
+    let mut grants = approval_state.grants.lock().unwrap_or_else(|e| e.into_inner());
+    grants.retain(|g| g.session_id.as_deref() != Some(session_id));
+}
+
+fn take_all_approvals(approval_state: &ApprovalState, session_id: Option<&str>) -> Vec<PendingApproval> {
+    let mut queue = approval_state.queue.lock().unwrap_or_else(|e| e.into_inner());
+    let now = now_ts();
+    queue.retain(|item| item.expires_at > now);
+    let (taken, kept): (Vec<_>, Vec<_>) = queue
+        .drain(..)
+        .partition(|item| session_id.is_none() || item.session_id.as_deref() == session_id);
+    *queue = kept;
+    persist_queue(approval_state, &queue);
+    taken
+}
+
+fn find_grant(
+    approval_state: &ApprovalState,
+    function_name: &str,
+    args: &Value,
+    working_dir: &Option<String>,
+    session_id: Option<&str>,
+) -> Option<ApprovalGrant> {
+    let mut grants = approval_state.grants.lock().unwrap_or_else(|e| e.into_inner());
+    let now = now_ts();
+    grants.retain(|g| g.expires_at.is_none_or(|exp| exp > now));
+    grants
+        .iter()
+        .find(|g| {
+            (g.session_id.is_none() || g.session_id.as_deref() == session_id)
+                && g.exact_args.as_ref().is_none_or(|exact| exact == args)
+                && policy::call_matches(&g.tool, &g.args, function_name, args, working_dir)
+        })
+        .cloned()
+}
+
+fn add_grant_to_state(
+    approval_state: &ApprovalState,
+    tool: String,
+    args: HashMap<String, String>,
+    exact_args: Option<Value>,
+    session_id: Option<String>,
+    duration_secs: Option<u64>,
+) -> ApprovalGrant {
+    let now = now_ts();
+    let grant = ApprovalGrant {
+        id: uuid::Uuid::new_v4().to_string(),
+        tool,
+        args,
+        exact_args,
+        session_id,
+        expires_at: duration_secs.map(|d| now + d),
+        created_at: now,
+    };
+    approval_state.grants.lock().unwrap_or_else(|e| e.into_inner()).push(grant.clone());
+    grant
+}
+
+fn parse_duration_secs(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let value: u64 = text[..split].parse().ok()?;
+    let unit = text[split..].trim();
+    let multiplier = match unit {
+        "s" | "sec" | "secs" | "second" | "seconds" => 1,
+        "" | "m" | "min" | "mins" | "minute" | "minutes" => 60,
+        "h" | "hr" | "hrs" | "hour" | "hours" => 3600,
+        _ => return None,
+    };
+    Some(value * multiplier)
+}
+
+// Parses standing grants typed into chat:
+//   allow write_file under src/ for this session
+//   allow cargo commands for 30 minutes
+//   allow execute_command command=npm for 2h
+// Returns (tool, arg patterns, duration in seconds; None = rest of session).
+fn parse_grant_prompt(prompt: &str) -> Option<(String, HashMap<String, String>, Option<u64>)> {
+    if !prompt.to_lowercase().starts_with("allow ") {
+        return None;
+    }
+    let (spec, duration) = prompt[6..].rsplit_once(" for ")?;
+    let duration = duration.trim().to_lowercase();
+    let duration_secs = match duration.as_str() {
+        "session" | "this session" | "the session" => None,
+        other => Some(parse_duration_secs(other)?),
+    };
+
+    let tokens: Vec<&str> = spec.split_whitespace().collect();
+    let mut args = HashMap::new();
+    let tool = match tokens.as_slice() {
+        [cmd, "commands"] | [cmd, "command"] => {
+            args.insert("command".to_string(), cmd.to_string());
+            return Some(("execute_command".to_string(), args, duration_secs));
+        }
+        [tool, ..] => tool.to_string(),
+        [] => return None,
+    };
+    let mut i = 1;
+    while i < tokens.len() {
+        if tokens[i] == "under" {
+            let dir = tokens.get(i + 1)?.trim_end_matches(['/', '\\']);
+            args.insert("path".to_string(), format!("{}/*", dir));
+            i += 2;
+        } else if let Some((name, pattern)) = tokens[i].split_once('=') {
+            args.insert(name.to_string(), pattern.to_string());
+            i += 1;
+        } else {
+            return None;
+        }
+    }
+    Some((tool, args, duration_secs))
+}
+
+// Runs an approved call that has no suspended turn to resume (e.g. after a restart).
+async fn run_approved(
+    app: &tauri::AppHandle,
+    pending: &PendingApproval,
+    audit_state: &tauri::State<'_, AuditState>,
+) -> String {
+    if pending.args_redacted {
+        return format!("Not run {}: its arguments contained secrets, which are not kept across restarts. Ask again.", pending.action);
+    }
+    let result = dispatch_tool(app, &pending.function_name, &pending.args, &pending.working_dir, pending.id.clone(), audit_state, CallOrigin {
+        session_id: pending.session_id.as_ref(),
+        turn_id: None,
+        approval: format!("approval:{}", pending.id),
+    }).await;
+    match result {
+        Ok(msg) => match msg {
+            MessageContent::Text(t) => format!("Approved {}: {}", pending.action, t),
+            MessageContent::Parts(_) => format!("Approved {}: (structured output)", pending.action),
+        },
+        Err(e) => format!("Failed {}: {}", pending.action, e),
+    }
+}
+
+fn take_suspended_turn(approval_state: &ApprovalState, approval_id: &str) -> Option<SuspendedTurn> {
+    let mut suspended = approval_state.suspended.lock().unwrap_or_else(|e| e.into_inner());
+    suspended.remove(approval_id)
+}
+
+fn take_suspended_turn_for_session(approval_state: &ApprovalState, session_id: Option<&str>) -> Option<SuspendedTurn> {
+    let mut suspended = approval_state.suspended.lock().unwrap_or_else(|e| e.into_inner());
+    let key = suspended
+        .iter()
+        .find(|(_, turn)| turn.session_id.as_deref() == session_id)
+        .map(|(k, _)| k.clone())?;
+    suspended.remove(&key)
+}
+
+// Tool output as the model will see it; secrets are masked when `redact_tool_output` is on.
+fn model_tool_output(settings: &AppSettings, output: Result<MessageContent, String>) -> MessageContent {
+    let content = output.unwrap_or_else(|e| MessageContent::Text(format!("Error: {}", e)));
+    match content {
+        MessageContent::Text(t) if settings.redact_tool_output => MessageContent::Text(redact::redact(&t)),
+        other => other,
+    }
+}
+
+fn tool_reply(tool_call_id: &str, content: MessageContent) -> Message {
+    Message {
+        role: "tool".into(),
+        content: Some(content),
+        tool_calls: None,
+        tool_call_id: Some(tool_call_id.to_string()),
+    }
+}
 
-    if is_sensitive_tool(function_name) {
-        Some("Sensitive action requires explicit approval".to_string())
+fn pop_approval(approval_state: &ApprovalState, id: &str) -> Option<PendingApproval> {
+    let mut queue = approval_state.queue.lock().ok()?;
+    let now = now_ts();
+    let mut idx = None;
+    for (i, item) in queue.iter().enumerate() {
+        if item.expires_at <= now {
+            continue;
+        }
+        if item.id == id {
+            idx = Some(i);
+            break;
+        }
+    }
+    if let Some(i) = idx {
+        let item = queue.remove(i);
+        persist_queue(approval_state, &queue);
+        Some(item)
     } else {
         None
     }
 }
 
+// Who a tool call runs for; carried into the tool_dispatch span and the audit entry.
+struct CallOrigin<'a> {
+    session_id: Option<&'a String>,
+    turn_id: Option<&'a str>,
+    // How the call was authorized, e.g. "policy:builtin", "grant:<id>", "approval:<id>"
+    approval: String,
+}
+
+#[tracing::instrument(
+    name = "tool_dispatch",
+    skip_all,
+    fields(
+        tool = %function_name,
+        args_hash = %logging::args_hash(args),
+        session_id = origin.session_id.map(|s| s.as_str()),
+        turn_id = origin.turn_id,
+        approval = %origin.approval,
+    )
+)]
 async fn dispatch_tool(
     app: &tauri::AppHandle,
     function_name: &str,
     args: &Value,
     working_dir: &Option<String>,
     id: String,
-    structured_logs: bool,
     audit_state: &tauri::State<'_, crate::audit::AuditState>,
-    session_id: Option<&String>,
+    origin: CallOrigin<'_>,
 ) -> Result<MessageContent, String> {
     
     // Log tool call start
-    if let Some(sid) = session_id {
-        logging::log(app, sid, "TOOL_START", &format!("Tool: {}, Args: {}", function_name, args));
-    }
+    tracing::info!(target: SESSION_TARGET, kind = "TOOL_START", "Tool: {}, Args: {}", function_name, redact::redact_value(args));
 
     let start = std::time::Instant::now();
     let tool_output = match function_name {
@@ -393,18 +745,23 @@ async fn dispatch_tool(
     let duration_ms = start.elapsed().as_millis();
     
     // Log tool result
-    if let Some(sid) = session_id {
+    {
         let result_summary = match &tool_output {
-            Ok(MessageContent::Text(t)) => t.chars().take(500).collect::<String>(), // Truncate for log
+            Ok(MessageContent::Text(t)) => redact::redact(t).chars().take(500).collect::<String>(), // Redact, then truncate for log
             Ok(MessageContent::Parts(_)) => "(structured content)".to_string(),
             Err(e) => format!("Error: {}", e),
         };
-        logging::log(app, sid, "TOOL_END", &format!("Tool: {}, Status: {}, Duration: {}ms, Output: {}", 
-            function_name, 
-            if tool_output.is_ok() { "Success" } else { "Failed" }, 
-            duration_ms, 
+        tracing::info!(
+            target: SESSION_TARGET,
+            kind = "TOOL_END",
+            duration_ms = duration_ms as u64,
+            status = if tool_output.is_ok() { "success" } else { "error" },
+            "Tool: {}, Status: {}, Duration: {}ms, Output: {}",
+            function_name,
+            if tool_output.is_ok() { "Success" } else { "Failed" },
+            duration_ms,
             result_summary
-        ));
+        );
     }
 
     if !matches!(function_name, "set_plan" | "complete_step") {
@@ -420,23 +777,29 @@ async fn dispatch_tool(
             duration_ms,
             kind: "tool".into(),
         });
-        let _ = audit::append_audit(
+        if let Err(e) = audit::append_audit(
             function_name,
             if tool_output.is_ok() { "success" } else { "error" },
-            &args.to_string(),
+            &redact::redact_value(args).to_string(),
             duration_ms,
-            working_dir.clone(),
-            structured_logs,
+            audit::AuditOrigin {
+                working_dir: working_dir.clone(),
+                session_id: origin.session_id.cloned(),
+                approval: Some(origin.approval),
+            },
             audit_state,
-        );
+        ) {
+            audit::report_audit_failure(app, format!("Audit entry for '{}' was not written: {}", function_name, e));
+        }
     }
 
     tool_output
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
 pub enum AgentMode {
     Plan,
+    #[default]
     Build,
 }
 
@@ -451,33 +814,82 @@ impl Default for AgentState {
     fn default() -> Self {
         Self {
             history: Mutex::new(Vec::new()),
-            mode: Mutex::new(AgentMode::Build), // Default to Build mode for power
+            mode: Mutex::new(AgentMode::default()), // Default to Build mode for power
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct PendingApproval {
     pub id: String,
     pub function_name: String,
     pub action: String,
     pub args: Value,
+    #[serde(rename = "workspace")]
     pub working_dir: Option<String>,
     pub expires_at: u64,
     pub session_id: Option<String>,
+    #[serde(default)]
+    pub reason: String,
+    // "low" / "medium" / "high"
+    #[serde(default)]
+    pub risk: String,
+    // Unified diff for write_file, command line for execute_command
+    #[serde(default)]
+    pub preview: String,
+    // The model's call this approval answers; None for approvals saved by older builds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    // Set on approvals reloaded from disk whose args lost secrets to redaction
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub args_redacted: bool,
+}
+
+// A conversation paused on an approval. `history` runs up to the assistant message
+// whose tool call is awaiting approval; `queued` holds its remaining, unprocessed calls.
+#[derive(Clone)]
+pub struct SuspendedTurn {
+    pub session_id: Option<String>,
+    pub working_dir: Option<String>,
+    pub tool_call_id: String,
+    pub turn_id: String,
+    pub history: Vec<Message>,
+    pub queued: Vec<ToolCall>,
+}
+
+// Standing approval: calls matching `tool`/`args` skip the approval prompt
+// until the grant expires or is revoked. `expires_at: None` lasts for the session.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ApprovalGrant {
+    pub id: String,
+    pub tool: String,
+    #[serde(default)]
+    pub args: HashMap<String, String>,
+    // When set, only a call with exactly these arguments matches
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exact_args: Option<Value>,
+    pub session_id: Option<String>,
+    pub expires_at: Option<u64>,
+    pub created_at: u64,
 }
 
 #[derive(Default)]
 pub struct ApprovalState {
+    pub path: Mutex<Option<PathBuf>>,
     pub queue: Mutex<Vec<PendingApproval>>,
+    pub grants: Mutex<Vec<ApprovalGrant>>,
+    // Keyed by approval id
+    pub suspended: Mutex<HashMap<String, SuspendedTurn>>,
 }
 
 use crate::skills::SkillState;
 use crate::audit::AuditState;
+use crate::policy::{self, Decision, PolicyContext, PolicyRule, PolicyState};
 
 #[tauri::command]
 pub fn set_agent_mode(
     state: tauri::State<'_, AgentState>,
+    session_state: tauri::State<'_, SessionState>,
     mode: String
 ) -> Result<String, String> {
     let new_mode = match mode.to_lowercase().as_str() {
@@ -486,6 +898,11 @@ pub fn set_agent_mode(
         _ => return Err("Invalid mode. Use 'plan' or 'build'".to_string()),
     };
     *state.mode.lock().map_err(|e| e.to_string())? = new_mode.clone();
+    // The current session remembers its mode across switches
+    let current = session_state.current_session_id.lock().map_err(|e| e.to_string())?.clone();
+    if let Some(sid) = current {
+        let _ = update_session(&sid, |session| session.mode = Some(new_mode.clone()));
+    }
     Ok(format!("Agent mode set to {:?}", new_mode))
 }
 
@@ -496,108 +913,817 @@ pub fn get_agent_mode(state: tauri::State<'_, AgentState>) -> Result<String, Str
 }
 
 #[tauri::command]
-pub async fn chat(
-    app: tauri::AppHandle,
-    prompt: String,
-    working_dir: Option<String>,
-    session_id: Option<String>, // New: specific session
-    state: tauri::State<'_, AgentState>, // Legacy
-    session_state: tauri::State<'_, SessionState>, // New: Persistence
-    settings_state: tauri::State<'_, SettingsState>,
+pub fn list_grants(
+    session_id: Option<String>,
     approval_state: tauri::State<'_, ApprovalState>,
-    skill_state: tauri::State<'_, SkillState>,
-    audit_state: tauri::State<'_, AuditState>,
-) -> Result<String, String> {
-    
-    // Fast-path approval/deny commands
-    let trimmed = prompt.trim().to_lowercase();
-    if let Some(rest) = trimmed.strip_prefix("approve ") {
-        let id = rest.trim();
-        if let Some(pending) = pop_approval(&approval_state, id) {
-            // NOTE: We don't have session_id easily here for logging without more plumbing, 
-            // but approvals are secondary to the main flow.
-            let result = dispatch_tool(&app, &pending.function_name, &pending.args, &pending.working_dir, pending.id.clone(), false, &audit_state, pending.session_id.as_ref()).await;
-            let _ = app.emit("approval_resolved", json!({"id": id, "status": "approved"}));
-            return Ok(match result {
-                Ok(msg) => match msg {
-                    MessageContent::Text(t) => format!("Approved {}: {}", pending.action, t),
-                    MessageContent::Parts(_) => format!("Approved {}: (structured output)", pending.action),
-                },
-                Err(e) => format!("Failed {}: {}", pending.action, e),
-            });
-        } else {
-            return Ok(format!("No pending approval for id '{}'", id));
-        }
-    } else if let Some(rest) = trimmed.strip_prefix("deny ") {
-        let id = rest.trim();
-        if pop_approval(&approval_state, id).is_some() {
-            let _ = app.emit("approval_resolved", json!({"id": id, "status": "denied"}));
-            return Ok(format!("Denied request {}", id));
-        } else {
-            return Ok(format!("No pending approval for id '{}'", id));
-        }
-    }
-
-    // 1. Get Settings
-    let settings = {
-        let settings = settings_state.0.lock().map_err(|e| e.to_string())?;
-        settings.clone()
-    };
-    let provider = settings.provider.clone();
-    let api_key = if !settings.openai_api_key.is_empty() {
-        settings.openai_api_key.clone()
-    } else {
-        settings.api_key.clone()
-    };
-    let model = settings.model.clone();
-
-    if provider != "openai" {
-        return Ok(format!("Provider '{}' not supported yet.", provider));
-    }
+) -> Result<Vec<ApprovalGrant>, String> {
+    let mut grants = approval_state.grants.lock().map_err(|e| e.to_string())?;
+    let now = now_ts();
+    grants.retain(|g| g.expires_at.is_none_or(|exp| exp > now));
+    Ok(grants
+        .iter()
+        .filter(|g| session_id.is_none() || g.session_id.is_none() || g.session_id == session_id)
+        .cloned()
+        .collect())
+}
 
-    if api_key.is_empty() {
-        return Ok("Please set your OpenAI API Key in Settings.".to_string());
+#[tauri::command]
+pub fn add_grant(
+    tool: String,
+    args: Option<HashMap<String, String>>,
+    session_id: Option<String>,
+    duration_secs: Option<u64>,
+    approval_state: tauri::State<'_, ApprovalState>,
+) -> Result<ApprovalGrant, String> {
+    if duration_secs.is_none() && session_id.is_none() {
+        return Err("A grant needs a session or a duration".to_string());
     }
+    Ok(add_grant_to_state(&approval_state, tool, args.unwrap_or_default(), None, session_id, duration_secs))
+}
 
-    // 2. Resolve Session
-    // If session_id provided, use it. Else check active session. If none, create temp/default.
-    let active_session_id = {
-        let mut current = session_state.current_session_id.lock().map_err(|e| e.to_string())?;
-        if let Some(sid) = session_id {
-            *current = Some(sid.clone());
-            Some(sid)
-        } else {
-            current.clone()
-        }
-    };
-
-    if let Some(sid) = &active_session_id {
-        logging::log(&app, sid, "USER", &prompt);
+#[tauri::command]
+pub fn revoke_grant(id: String, approval_state: tauri::State<'_, ApprovalState>) -> Result<(), String> {
+    let mut grants = approval_state.grants.lock().map_err(|e| e.to_string())?;
+    let before = grants.len();
+    grants.retain(|g| g.id != id);
+    if grants.len() == before {
+        return Err("Grant not found".to_string());
     }
+    Ok(())
+}
 
-    // Load History
-    let mut history: Vec<Message>;
-    if let Some(sid) = &active_session_id {
-        // Load from disk/memory
-        let dir = crate::session_manager::get_sessions_dir();
-        let path = dir.join(format!("{}.json", sid));
-        if path.exists() {
-            let content = std::fs::read_to_string(path).unwrap_or_default();
-            let session: Session = serde_json::from_str(&content).unwrap_or_else(|_| Session {
-                id: sid.clone(), title: "Error".into(), messages: vec![], created_at: 0, updated_at: 0, pinned: false
+fn tool_definitions() -> Vec<Value> {
+    vec![
+        json!({ "type": "function", "function": { "name": "set_plan", "description": "Create a visual plan", "parameters": { "type": "object", "properties": { "steps": { "type": "array", "items": { "type": "string" } } }, "required": ["steps"] } } }),
+        json!({ "type": "function", "function": { "name": "complete_step", "description": "Mark step complete", "parameters": { "type": "object", "properties": { "step_index": { "type": "integer" } }, "required": ["step_index"] } } }),
+        json!({ "type": "function", "function": { "name": "list_dir", "description": "List files", "parameters": { "type": "object", "properties": { "path": { "type": "string" } }, "required": ["path"] } } }),
+        json!({ "type": "function", "function": { "name": "read_file", "description": "Read file", "parameters": { "type": "object", "properties": { "path": { "type": "string" } }, "required": ["path"] } } }),
+        json!({ "type": "function", "function": { "name": "write_file", "description": "Write file", "parameters": { "type": "object", "properties": { "path": { "type": "string" }, "content": { "type": "string" } }, "required": ["path", "content"] } } }),
+        json!({ "type": "function", "function": { "name": "execute_command", "description": "Run command", "parameters": { "type": "object", "properties": { "command": { "type": "string" }, "args": { "type": "array", "items": { "type": "string" } } }, "required": ["command", "args"] } } }),
+        json!({ "type": "function", "function": { "name": "open_app", "description": "Open a file or app", "parameters": { "type": "object", "properties": { "path": { "type": "string" } }, "required": ["path"] } } }),
+        json!({ "type": "function", "function": { "name": "fetch_url", "description": "Fetch content from URL", "parameters": { "type": "object", "properties": { "url": { "type": "string" }, "expected_hash": { "type": "string", "description": "Optional SHA256 hash to verify content" } }, "required": ["url"] } } }),
+        json!({ "type": "function", "function": { "name": "get_system_stats", "description": "Get CPU/Memory usage", "parameters": { "type": "object", "properties": {}, "required": [] } } }),
+        json!({ "type": "function", "function": { "name": "search_files", "description": "Search text in files", "parameters": { "type": "object", "properties": { "query": { "type": "string" }, "path": { "type": "string" } }, "required": ["query", "path"] } } }),
+        json!({ "type": "function", "function": { "name": "search_web", "description": "Search web (opens browser)", "parameters": { "type": "object", "properties": { "query": { "type": "string" } }, "required": ["query"] } } }),
+        json!({ "type": "function", "function": { "name": "keyboard_type", "description": "Simulate typing text", "parameters": { "type": "object", "properties": { "text": { "type": "string" } }, "required": ["text"] } } }),
+        json!({ "type": "function", "function": { "name": "keyboard_press", "description": "Simulate key press (Enter, Tab, etc)", "parameters": { "type": "object", "properties": { "key": { "type": "string" } }, "required": ["key"] } } }),
+        json!({ "type": "function", "function": { "name": "mouse_move", "description": "Move mouse to coordinates", "parameters": { "type": "object", "properties": { "x": { "type": "integer" }, "y": { "type": "integer" } }, "required": ["x", "y"] } } }),
+        json!({ "type": "function", "function": { "name": "mouse_click", "description": "Click mouse button", "parameters": { "type": "object", "properties": { "button": { "type": "string", "enum": ["left", "right", "middle"] } }, "required": ["button"] } } }),
+        json!({ "type": "function", "function": { "name": "get_screenshot", "description": "Get current screen as base64 image", "parameters": { "type": "object", "properties": {}, "required": [] } } }),
+        json!({ "type": "function", "function": { "name": "wait", "description": "Wait for N milliseconds", "parameters": { "type": "object", "properties": { "milliseconds": { "type": "integer" } }, "required": ["milliseconds"] } } }),
+        json!({ "type": "function", "function": { "name": "create_docx", "description": "Create a Word DOCX file from text content", "parameters": { "type": "object", "properties": { "content": { "type": "string" }, "filename": { "type": "string" } }, "required": ["content", "filename"] } } }),
+        json!({ "type": "function", "function": { "name": "create_slide_deck", "description": "Create a Reveal.js slide deck (HTML) from text", "parameters": { "type": "object", "properties": { "content": { "type": "string" }, "filename": { "type": "string" } }, "required": ["content", "filename"] } } }),
+        json!({ "type": "function", "function": { "name": "find_file_smart", "description": "Recursively find files by name (fuzzy)", "parameters": { "type": "object", "properties": { "query": { "type": "string" }, "path": { "type": "string" } }, "required": ["query", "path"] } } })
+    ]
+}
+
+// Everything a turn needs to call the model and dispatch tools; shared by
+// `chat` and by turns resumed after an approval.
+#[derive(Clone)]
+struct TurnContext<'a> {
+    app: &'a tauri::AppHandle,
+    client: Client,
+    api_key: String,
+    model: String,
+    settings: AppSettings,
+    agent_mode: AgentMode,
+    working_dir: Option<String>,
+    session_id: Option<String>,
+    // Groups the session-log events of one user turn (kept across an approval pause)
+    turn_id: String,
+    // Skill ids the session is bound to; None follows the global skill toggles
+    enabled_skills: Option<Vec<String>>,
+    skill_state: &'a tauri::State<'a, SkillState>,
+    approval_state: &'a tauri::State<'a, ApprovalState>,
+    audit_state: &'a tauri::State<'a, AuditState>,
+    policy_state: &'a tauri::State<'a, PolicyState>,
+}
+
+enum TurnOutcome {
+    Finished(String),
+    AwaitingApproval(String),
+    Unavailable(String),
+}
+
+// Processes queued tool calls in order. Returns the approval notice if a call
+// needs approval; the turn is then stored in `ApprovalState.suspended`.
+async fn run_tool_calls(ctx: &TurnContext<'_>, history: &mut Vec<Message>, queued: &mut Vec<ToolCall>) -> Option<String> {
+    while !queued.is_empty() {
+        let tool_call = queued.remove(0);
+        let function_name = &tool_call.function.name;
+        let args: Value = serde_json::from_str(&tool_call.function.arguments).unwrap_or(json!({}));
+        if !crate::skills::is_tool_enabled(ctx.skill_state, ctx.enabled_skills.as_deref(), function_name) {
+            let msg = format!("Tool '{}' is disabled in Skills settings.", function_name);
+            history.push(tool_reply(&tool_call.id, MessageContent::Text(msg)));
+            continue;
+        }
+
+        // Check policy (agent mode, read-only, workspace scope, policy.toml rules)
+        let verdict = policy::evaluate(ctx.policy_state, function_name, &args, &PolicyContext {
+            mode: &ctx.agent_mode,
+            read_only: ctx.settings.read_only,
+            working_dir: &ctx.working_dir,
+            session_id: ctx.session_id.as_deref(),
+        });
+
+        if verdict.decision == Decision::Deny {
+            let msg = format!("Tool '{}' denied by {:?} policy: {}", function_name, verdict.layer, verdict.reason);
+            history.push(tool_reply(&tool_call.id, MessageContent::Text(msg)));
+            continue;
+        }
+
+        let grant = if verdict.grantable() {
+            find_grant(ctx.approval_state, function_name, &args, &ctx.working_dir, ctx.session_id.as_deref())
+        } else {
+            None
+        };
+
+        if verdict.decision == Decision::Ask && grant.is_none() {
+            let (approval_id, notice) = request_approval(ctx, function_name, args.clone(), verdict.reason, &tool_call.id);
+            let mut suspended = ctx.approval_state.suspended.lock().unwrap_or_else(|e| e.into_inner());
+            suspended.insert(approval_id, SuspendedTurn {
+                session_id: ctx.session_id.clone(),
+                working_dir: ctx.working_dir.clone(),
+                tool_call_id: tool_call.id.clone(),
+                turn_id: ctx.turn_id.clone(),
+                history: history.clone(),
+                queued: std::mem::take(queued),
             });
-            history = session.messages;
+            return Some(notice);
+        }
+
+        let approval = match &grant {
+            Some(g) => format!("grant:{}", g.id),
+            None => format!("policy:{:?}", verdict.layer).to_lowercase(),
+        };
+        let id = uuid::Uuid::new_v4().to_string();
+        let tool_output = dispatch_tool(ctx.app, function_name, &args, &ctx.working_dir, id.clone(), ctx.audit_state, CallOrigin {
+            session_id: ctx.session_id.as_ref(),
+            turn_id: Some(&ctx.turn_id),
+            approval,
+        }).await;
+
+        history.push(tool_reply(&tool_call.id, model_tool_output(&ctx.settings, tool_output)));
+    }
+    None
+}
+
+fn turn_span(ctx: &TurnContext<'_>) -> tracing::Span {
+    tracing::info_span!(
+        "chat_turn",
+        session_id = ctx.session_id.as_deref(),
+        turn_id = %ctx.turn_id,
+        mode = ?ctx.agent_mode,
+    )
+}
+
+// The model/tool loop. `queued` carries tool calls left over from a resumed turn.
+async fn run_conversation(ctx: &TurnContext<'_>, history: &mut Vec<Message>, mut queued: Vec<ToolCall>) -> Result<TurnOutcome, String> {
+    let tools = tool_definitions();
+    let mut final_response = String::new();
+
+    if let Some(notice) = run_tool_calls(ctx, history, &mut queued).await {
+        return Ok(TurnOutcome::AwaitingApproval(notice));
+    }
+
+    for _ in 0..10 {
+        let request_body = json!({
+            "model": ctx.model,
+            "messages": history,
+            "tools": tools,
+            "tool_choice": "auto"
+        });
+
+        let api_start = std::time::Instant::now();
+        let provider_span = tracing::info_span!("provider_call", provider = %ctx.settings.provider, model = %ctx.model);
+        let mut last_err: Option<String> = None;
+        let mut body_opt: Option<OpenAIChatResponse> = None;
+        async {
+            for attempt in 0..3 {
+                let res = ctx.client.post("https://api.openai.com/v1/chat/completions")
+                    .header("Authorization", format!("Bearer {}", ctx.api_key))
+                    .json(&request_body)
+                    .send()
+                    .await;
+
+                match res {
+                    Ok(resp) => {
+                        if !resp.status().is_success() {
+                            last_err = Some(format!("API status {}", resp.status()));
+                        } else {
+                            match resp.json::<OpenAIChatResponse>().await {
+                                Ok(body) => {
+                                    body_opt = Some(body);
+                                    break;
+                                }
+                                Err(e) => last_err = Some(format!("Parse failed: {}", e)),
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        last_err = Some(format!("Request failed: {}", e));
+                    }
+                }
+                let backoff = 2u64.pow(attempt) * 300;
+                tokio::time::sleep(Duration::from_millis(backoff)).await;
+            }
+        }
+        .instrument(provider_span.clone())
+        .await;
+
+        let body = match body_opt {
+            Some(b) => b,
+            None => {
+                let msg = format!("Chat request failed: {}", last_err.unwrap_or_else(|| "unknown error".into()));
+                let _ = ctx.app.emit("telemetry", TelemetryEvent {
+                    tool: "openai_chat".into(),
+                    status: "error".into(),
+                    duration_ms: api_start.elapsed().as_millis(),
+                    kind: "api".into(),
+                });
+                provider_span.in_scope(|| {
+                    tracing::info!(
+                        target: SESSION_TARGET,
+                        kind = "API",
+                        duration_ms = api_start.elapsed().as_millis() as u64,
+                        status = "error",
+                        "{}",
+                        redact::redact(&msg)
+                    )
+                });
+                return Ok(TurnOutcome::Unavailable(format!("Offline or unavailable. {}", msg)));
+            }
+        };
+        let api_latency = api_start.elapsed().as_millis();
+        let _ = ctx.app.emit("telemetry", TelemetryEvent {
+            tool: "openai_chat".into(),
+            status: "success".into(),
+            duration_ms: api_latency,
+            kind: "api".into(),
+        });
+        let usage = body.usage.as_ref();
+        provider_span.in_scope(|| {
+            tracing::info!(
+                target: SESSION_TARGET,
+                kind = "API",
+                duration_ms = api_latency as u64,
+                status = "success",
+                prompt_tokens = usage.map(|u| u.prompt_tokens),
+                completion_tokens = usage.map(|u| u.completion_tokens),
+                total_tokens = usage.map(|u| u.total_tokens),
+                "Model: {}, Latency: {}ms",
+                ctx.model,
+                api_latency
+            )
+        });
+        let choice = body.choices.first().ok_or("No response")?;
+        let message = &choice.message;
+
+        history.push(message.clone());
+
+        if let Some(tool_calls) = &message.tool_calls {
+            queued = tool_calls.clone();
+            if let Some(notice) = run_tool_calls(ctx, history, &mut queued).await {
+                return Ok(TurnOutcome::AwaitingApproval(notice));
+            }
+            continue;
+        }
+
+        // Handle response content which might be just text or null
+        if let Some(content) = &message.content {
+            match content {
+                MessageContent::Text(t) => {
+                    final_response = t.clone();
+                    // Log assistant response
+                    tracing::info!(target: SESSION_TARGET, kind = "ASSISTANT", "{}", redact::redact(t));
+                },
+                MessageContent::Parts(parts) => {
+                    // Concatenate text parts for simple string return
+                    final_response = parts.iter().filter_map(|p| p.text.clone()).collect::<Vec<_>>().join("\n");
+                    // Log assistant response
+                    tracing::info!(target: SESSION_TARGET, kind = "ASSISTANT", "{}", redact::redact(&final_response));
+                }
+            }
+        }
+        break;
+    }
+
+    Ok(TurnOutcome::Finished(final_response))
+}
+
+// Records what the turn ran with so reopening the session restores it. The model is bound
+// on the first turn only; `set_session_model` changes it afterwards.
+fn bind_session(session: &mut Session, ctx: &TurnContext<'_>) {
+    if ctx.working_dir.is_some() {
+        session.working_dir = ctx.working_dir.clone();
+    }
+    session.mode = Some(ctx.agent_mode.clone());
+    if session.model.is_none() {
+        session.model = Some(ctx.model.clone());
+    }
+}
+
+// Streams the response to the UI and persists the history.
+async fn finish_turn(ctx: &TurnContext<'_>, state: &AgentState, history: Vec<Message>, outcome: &TurnOutcome) -> Result<String, String> {
+    let final_response = match outcome {
+        TurnOutcome::Finished(text) | TurnOutcome::AwaitingApproval(text) | TurnOutcome::Unavailable(text) => text.clone(),
+    };
+
+    // Stream final response as tokens
+    if !final_response.is_empty() {
+        let chars: Vec<char> = final_response.chars().collect();
+        for chunk in chars.chunks(5) {
+            let token: String = chunk.iter().collect();
+            let _ = ctx.app.emit("chat_stream", json!({"token": token, "done": false}));
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let _ = ctx.app.emit("chat_stream", json!({"done": true}));
+    }
+
+    save_turn(ctx, state, history, outcome)?;
+
+    Ok(final_response)
+}
+
+// Persists the turn's history to its session, or to the in-memory history when there is none.
+fn save_turn(ctx: &TurnContext<'_>, state: &AgentState, history: Vec<Message>, outcome: &TurnOutcome) -> Result<(), String> {
+    let Some(sid) = &ctx.session_id else {
+        *state.history.lock().map_err(|e| e.to_string())? = history;
+        return Ok(());
+    };
+    // Stored losslessly (tool calls and results included) so a reopened session can resume;
+    // the vault seals it at rest when encryption is on and export applies the session's privacy level.
+    let mut transcript = history;
+    // The notice is shown in the transcript but kept out of the model history,
+    // where it would separate the pending tool call from its result.
+    if let TurnOutcome::AwaitingApproval(notice) = outcome {
+        transcript.push(Message {
+            role: "assistant".into(),
+            content: Some(MessageContent::Text(notice.clone())),
+            tool_calls: None,
+            tool_call_id: None,
+        });
+    }
+    let saved = store_transcript(sid, transcript, |session| bind_session(session, ctx));
+    if let (Some(session), TurnOutcome::Finished(_)) = (saved, outcome) {
+        if summary_due(&session) {
+            spawn_session_summary(ctx, session.id);
+        }
+    }
+    Ok(())
+}
+
+// Only the transcript changes; title, pin and created_at stay as the user left them.
+fn store_transcript(sid: &str, transcript: Vec<Message>, bind: impl Fn(&mut Session)) -> Option<Session> {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    let updated = update_session(sid, |session| {
+        session.messages = transcript.clone();
+        session.updated_at = now;
+        bind(session);
+    });
+    match updated {
+        Ok(session) => Some(session),
+        Err(_) => {
+            // The record is gone (e.g. deleted mid-turn); keep the transcript under a fresh one
+            let mut session = Session {
+                id: sid.to_string(),
+                title: DEFAULT_TITLE.to_string(),
+                messages: transcript,
+                created_at: now,
+                updated_at: now,
+                ..Default::default()
+            };
+            bind(&mut session);
+            save_session_to_disk(&session).ok().map(|_| session)
+        }
+    }
+}
+
+const SUMMARY_REFRESH_MESSAGES: usize = 6;
+const SUMMARY_MESSAGE_CHARS: usize = 1_000;
+const TITLE_MAX_CHARS: usize = 60;
+
+#[derive(Deserialize)]
+struct SessionDigest {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    summary: String,
+}
+
+// A title is due after the first exchange; the summary is refreshed every few messages.
+fn summary_due(session: &Session) -> bool {
+    has_placeholder_title(session)
+        || session.messages.len() >= session.summarized_through + SUMMARY_REFRESH_MESSAGES
+}
+
+// User and assistant text since the last summary; tool output and the system prompt stay out.
+fn summary_transcript(messages: &[Message]) -> String {
+    messages
+        .iter()
+        .filter(|m| m.role == "user" || m.role == "assistant")
+        .filter_map(|m| {
+            let text = match m.content.as_ref()? {
+                MessageContent::Text(t) => t.clone(),
+                MessageContent::Parts(parts) => parts.iter().filter_map(|p| p.text.clone()).collect::<Vec<_>>().join("\n"),
+            };
+            if text.trim().is_empty() {
+                return None;
+            }
+            let text: String = text.chars().take(SUMMARY_MESSAGE_CHARS).collect();
+            Some(format!("{}: {}", m.role, text))
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+// Runs in the background so the reply is not held up; on failure the session keeps its
+// current title and summary and the next turn tries again.
+fn spawn_session_summary(ctx: &TurnContext<'_>, session_id: String) {
+    let app = ctx.app.clone();
+    let client = ctx.client.clone();
+    let api_key = ctx.api_key.clone();
+    let model = ctx.model.clone();
+    tauri::async_runtime::spawn(async move {
+        match refresh_session_summary(&client, &api_key, &model, &session_id).await {
+            Ok(session) => {
+                let _ = app.emit("session_updated", json!({"id": session.id, "title": session.title, "summary": session.summary}));
+            }
+            Err(e) => {
+                tracing::info!(target: SESSION_TARGET, session_id = session_id.as_str(), kind = "SUMMARY", status = "error", "{}", redact::redact(&e));
+            }
+        }
+    });
+}
+
+async fn refresh_session_summary(client: &Client, api_key: &str, model: &str, session_id: &str) -> Result<Session, String> {
+    let session = crate::session_manager::load_session(session_id)?;
+    let covered = session.messages.len();
+    let transcript = summary_transcript(&session.messages[session.summarized_through.min(covered)..]);
+    if transcript.is_empty() {
+        return Ok(session);
+    }
+    let previous = if session.summary.is_empty() { "(none)".to_string() } else { session.summary.clone() };
+    let request_body = json!({
+        "model": model,
+        "response_format": {"type": "json_object"},
+        "messages": [
+            {
+                "role": "system",
+                "content": "You maintain the title and summary of a chat session between a user and a desktop agent. \
+Reply with a JSON object {\"title\": string, \"summary\": string}. The title is at most 6 words, no quotes or trailing punctuation. \
+The summary is at most 3 sentences covering the goal, what was done and what is still open; fold the new messages into the previous summary."
+            },
+            {
+                "role": "user",
+                "content": format!("Previous summary: {}\n\nNew messages:\n{}", previous, transcript)
+            }
+        ]
+    });
+    let resp = client.post("https://api.openai.com/v1/chat/completions")
+        .header("Authorization", format!("Bearer {}", api_key))
+        .json(&request_body)
+        .send()
+        .await
+        .map_err(|e| format!("Summary request failed: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("Summary request failed: API status {}", resp.status()));
+    }
+    let body = resp.json::<OpenAIChatResponse>().await.map_err(|e| format!("Summary parse failed: {}", e))?;
+    let text = match body.choices.first().and_then(|c| c.message.content.as_ref()) {
+        Some(MessageContent::Text(t)) => t.clone(),
+        _ => return Err("Summary response was empty".to_string()),
+    };
+    let digest: SessionDigest = serde_json::from_str(&text).map_err(|e| format!("Summary parse failed: {}", e))?;
+    let title: String = digest.title.trim().trim_matches('"').chars().take(TITLE_MAX_CHARS).collect();
+
+    // Re-check under the session lock: the user may have renamed it while we were waiting
+    update_session(session_id, |session| {
+        if has_placeholder_title(session) && !title.is_empty() {
+            session.title = title;
+        }
+        if !digest.summary.trim().is_empty() {
+            session.summary = digest.summary.trim().to_string();
+        }
+        session.summarized_through = covered;
+    })
+}
+
+// Applies an approve/deny decision. If the call belongs to a suspended turn, its
+// result is answered against the original tool_call_id and the turn resumes.
+async fn apply_approval_decision(ctx: &TurnContext<'_>, state: &AgentState, pending: PendingApproval, approved: bool) -> Result<String, String> {
+    let status = if approved { "approved" } else { "denied" };
+    let _ = ctx.app.emit("approval_resolved", json!({"id": pending.id, "status": status}));
+
+    let Some(mut turn) = take_suspended_turn(ctx.approval_state, &pending.id) else {
+        let (reply, result) = if approved {
+            let result = run_approved(ctx.app, &pending, ctx.audit_state).await;
+            (result.clone(), result)
         } else {
-            history = Vec::new(); // Should not happen if created correctly
+            ("The user denied this action.".to_string(), format!("Denied request {}", pending.id))
+        };
+        close_out_approval(&pending, &reply, result.clone());
+        return Ok(result);
+    };
+
+    let content = if approved {
+        let output = dispatch_tool(ctx.app, &pending.function_name, &pending.args, &pending.working_dir, pending.id.clone(), ctx.audit_state, CallOrigin {
+            session_id: pending.session_id.as_ref(),
+            turn_id: Some(&ctx.turn_id),
+            approval: format!("approval:{}", pending.id),
+        }).await;
+        model_tool_output(&ctx.settings, output)
+    } else {
+        MessageContent::Text("The user denied this action.".to_string())
+    };
+    turn.history.push(tool_reply(&turn.tool_call_id, content));
+
+    let turn_ctx = TurnContext {
+        working_dir: turn.working_dir.clone(),
+        session_id: turn.session_id.clone(),
+        turn_id: turn.turn_id.clone(),
+        ..ctx.clone()
+    };
+    let mut history = turn.history;
+    let outcome = run_conversation(&turn_ctx, &mut history, turn.queued)
+        .instrument(turn_span(&turn_ctx))
+        .await?;
+    if let TurnOutcome::Unavailable(msg) = &outcome {
+        // The approved call has run and the suspended turn is gone; keep its result so the
+        // next turn does not answer it as "not executed"
+        save_turn(&turn_ctx, state, history, &outcome)?;
+        return Ok(msg.clone());
+    }
+    finish_turn(&turn_ctx, state, history, &outcome).await
+}
+
+// Managed state a turn works with, fetched from the app handle rather than passed to every command.
+struct TurnStates<'a> {
+    skill: tauri::State<'a, SkillState>,
+    approval: tauri::State<'a, ApprovalState>,
+    audit: tauri::State<'a, AuditState>,
+    policy: tauri::State<'a, PolicyState>,
+}
+
+impl<'a> TurnStates<'a> {
+    fn new(app: &'a tauri::AppHandle) -> Self {
+        Self {
+            skill: app.state(),
+            approval: app.state(),
+            audit: app.state(),
+            policy: app.state(),
         }
+    }
+}
+
+struct SessionBinding {
+    mode: AgentMode,
+    model: String,
+    working_dir: Option<String>,
+    enabled_skills: Option<Vec<String>>,
+}
+
+// What the session is bound to, falling back to the global mode and the configured model.
+fn session_binding(session_id: Option<&str>, state: &AgentState, settings: &AppSettings) -> Result<SessionBinding, String> {
+    let global_mode = state.mode.lock().map_err(|e| e.to_string())?.clone();
+    let bound = session_id.and_then(|sid| load_session_metadata(sid).ok()).unwrap_or_default();
+    Ok(SessionBinding {
+        mode: bound.mode.unwrap_or(global_mode),
+        model: bound.model.unwrap_or_else(|| settings.model.clone()),
+        working_dir: bound.working_dir,
+        enabled_skills: bound.enabled_skills,
+    })
+}
+
+// Err carries a message meant for the user rather than a failure.
+fn provider_api_key(settings: &AppSettings) -> Result<String, String> {
+    let provider = settings.provider.clone();
+    let api_key = if !settings.openai_api_key.is_empty() {
+        settings.openai_api_key.clone()
+    } else {
+        settings.api_key.clone()
+    };
+
+    if provider != "openai" {
+        return Err(format!("Provider '{}' not supported yet.", provider));
+    }
+
+    if api_key.is_empty() {
+        return Err("Please set your OpenAI API Key in Settings.".to_string());
+    }
+    Ok(api_key)
+}
+
+#[tauri::command]
+pub fn list_pending_approvals(
+    app: tauri::AppHandle,
+    session_id: Option<String>,
+    approval_state: tauri::State<'_, ApprovalState>,
+) -> Result<Vec<PendingApproval>, String> {
+    prune_expired_approvals(&app, &approval_state);
+    let queue = approval_state.queue.lock().map_err(|e| e.to_string())?;
+    Ok(queue
+        .iter()
+        .filter(|item| session_id.is_none() || item.session_id == session_id)
+        .cloned()
+        .collect())
+}
+
+#[tauri::command]
+pub async fn resolve_approval(
+    app: tauri::AppHandle,
+    id: String,
+    decision: String,
+    remember: Option<bool>,
+    state: tauri::State<'_, AgentState>,
+    settings_state: tauri::State<'_, SettingsState>,
+) -> Result<String, String> {
+    let TurnStates { skill: skill_state, approval: approval_state, audit: audit_state, policy: policy_state } = TurnStates::new(&app);
+    let approved = match decision.to_lowercase().as_str() {
+        "approve" | "approved" | "allow" => true,
+        "deny" | "denied" | "reject" => false,
+        _ => return Err("Invalid decision. Use 'approve' or 'deny'".to_string()),
+    };
+    prune_expired_approvals(&app, &approval_state);
+    let pending = pop_approval(&approval_state, &id).ok_or_else(|| format!("No pending approval for id '{}'", id))?;
+
+    if remember.unwrap_or(false) {
+        let patterns = remembered_patterns(&pending.function_name, &pending.args);
+        if approved {
+            if pending.session_id.is_some() {
+                // Approving `rm -rf build` once must not approve every `rm`
+                let exact = (pending.function_name == "execute_command").then(|| pending.args.clone());
+                add_grant_to_state(&approval_state, pending.function_name.clone(), patterns, exact, pending.session_id.clone(), None);
+            }
+        } else if let Some(sid) = &pending.session_id {
+            let mut session_rules = policy_state.session_rules.lock().map_err(|e| e.to_string())?;
+            session_rules.entry(sid.clone()).or_default().push(PolicyRule {
+                tool: pending.function_name.clone(),
+                args: patterns,
+                scope: None,
+                mode: None,
+                read_only: None,
+                decision: Decision::Deny,
+                reason: Some("Denied earlier in this session".to_string()),
+            });
+        }
+    }
+
+    let settings = settings_state.0.lock().map_err(|e| e.to_string())?.clone();
+    let api_key = match provider_api_key(&settings) {
+        Ok(key) => key,
+        Err(msg) => return Ok(msg),
+    };
+    let binding = session_binding(pending.session_id.as_deref(), &state, &settings)?;
+    let ctx = TurnContext {
+        app: &app,
+        client: Client::new(),
+        api_key,
+        model: binding.model,
+        settings,
+        agent_mode: binding.mode,
+        working_dir: pending.working_dir.clone(),
+        session_id: pending.session_id.clone(),
+        turn_id: uuid::Uuid::new_v4().to_string(),
+        enabled_skills: binding.enabled_skills,
+        skill_state: &skill_state,
+        approval_state: &approval_state,
+        audit_state: &audit_state,
+        policy_state: &policy_state,
+    };
+    apply_approval_decision(&ctx, &state, pending, approved).await
+}
+
+#[tauri::command]
+pub async fn chat(
+    app: tauri::AppHandle,
+    prompt: String,
+    working_dir: Option<String>,
+    session_id: Option<String>, // New: specific session
+    state: tauri::State<'_, AgentState>, // Legacy
+    session_state: tauri::State<'_, SessionState>, // New: Persistence
+    settings_state: tauri::State<'_, SettingsState>,
+) -> Result<String, String> {
+    let TurnStates { skill: skill_state, approval: approval_state, audit: audit_state, policy: policy_state } = TurnStates::new(&app);
+
+    // 1. Get Settings
+    let settings = {
+        let settings = settings_state.0.lock().map_err(|e| e.to_string())?;
+        settings.clone()
+    };
+    let api_key = match provider_api_key(&settings) {
+        Ok(key) => key,
+        Err(msg) => return Ok(msg),
+    };
+
+    // 2. Resolve Session
+    // If session_id provided, use it. Else check active session. If none, create temp/default.
+    let active_session_id = {
+        let mut current = session_state.current_session_id.lock().map_err(|e| e.to_string())?;
+        if let Some(sid) = session_id {
+            *current = Some(sid.clone());
+            Some(sid)
+        } else {
+            current.clone()
+        }
+    };
+
+    // 3. Mode, model, workspace and skills bound to the session (the passed working dir wins)
+    let binding = session_binding(active_session_id.as_deref(), &state, &settings)?;
+    let agent_mode = binding.mode;
+    let working_dir = working_dir.or(binding.working_dir);
+
+    let ctx = TurnContext {
+        app: &app,
+        client: Client::new(),
+        api_key,
+        model: binding.model,
+        settings,
+        agent_mode: agent_mode.clone(),
+        working_dir: working_dir.clone(),
+        session_id: active_session_id.clone(),
+        turn_id: uuid::Uuid::new_v4().to_string(),
+        enabled_skills: binding.enabled_skills,
+        skill_state: &skill_state,
+        approval_state: &approval_state,
+        audit_state: &audit_state,
+        policy_state: &policy_state,
+    };
+
+    // Fast-path approval/deny commands
+    prune_expired_approvals(&app, &approval_state);
+    let trimmed = prompt.trim().to_lowercase();
+    if trimmed == "approve all" || trimmed == "deny all" {
+        let approved = trimmed == "approve all";
+        let pending = take_all_approvals(&approval_state, active_session_id.as_deref());
+        if pending.is_empty() {
+            return Ok("No pending approvals".to_string());
+        }
+        let mut results = Vec::new();
+        for item in pending {
+            results.push(apply_approval_decision(&ctx, &state, item, approved).await?);
+        }
+        return Ok(results.join("\n"));
+    } else if let Some(rest) = trimmed.strip_prefix("approve ") {
+        let id = rest.trim();
+        if let Some(pending) = pop_approval(&approval_state, id) {
+            return apply_approval_decision(&ctx, &state, pending, true).await;
+        } else {
+            return Ok(format!("No pending approval for id '{}'", id));
+        }
+    } else if let Some(rest) = trimmed.strip_prefix("deny ") {
+        let id = rest.trim();
+        if let Some(pending) = pop_approval(&approval_state, id) {
+            return apply_approval_decision(&ctx, &state, pending, false).await;
+        } else {
+            return Ok(format!("No pending approval for id '{}'", id));
+        }
+    } else if let Some(rest) = trimmed.strip_prefix("revoke ") {
+        let id = rest.trim();
+        let mut grants = approval_state.grants.lock().map_err(|e| e.to_string())?;
+        let before = grants.len();
+        if id == "all" {
+            grants.clear();
+        } else {
+            grants.retain(|g| g.id != id);
+        }
+        return Ok(format!("Revoked {} grant(s)", before - grants.len()));
+    } else if let Some((tool, args, duration_secs)) = parse_grant_prompt(prompt.trim()) {
+        if duration_secs.is_none() && active_session_id.is_none() {
+            return Ok("Session-scoped grants need an active session".to_string());
+        }
+        let grant = add_grant_to_state(&approval_state, tool, args, None, active_session_id.clone(), duration_secs);
+        let _ = app.emit("grant_added", &grant);
+        let until = match grant.expires_at {
+            Some(exp) => format!("for {} minute(s)", (exp - grant.created_at).div_ceil(60)),
+            None => "for this session".to_string(),
+        };
+        return Ok(format!("Granted {} {} (grant id {}). Reply 'revoke {}' to withdraw.", grant.tool, until, grant.id, grant.id));
+    }
+
+    turn_span(&ctx).in_scope(|| tracing::info!(target: SESSION_TARGET, kind = "USER", "{}", redact::redact(&prompt)));
+
+    // Load History
+    let mut history: Vec<Message>;
+    if let Some(turn) = take_suspended_turn_for_session(&approval_state, active_session_id.as_deref()) {
+        // The user moved on without resolving the approval; close out the paused calls
+        // so the history stays valid. The approval itself can still be run on its own.
+        history = turn.history;
+        let skipped = "Not executed: the user sent a new message before approving.";
+        history.push(tool_reply(&turn.tool_call_id, MessageContent::Text(skipped.into())));
+        for call in &turn.queued {
+            history.push(tool_reply(&call.id, MessageContent::Text(skipped.into())));
+        }
+    } else if let Some(sid) = &active_session_id {
+        // Load from the session store; calls left waiting on an approval that was never settled
+        // in this history (e.g. by an older build) are closed out first
+        history = crate::session_manager::load_session(sid).map(|s| s.messages).unwrap_or_default();
+        answer_open_tool_calls(&mut history, None, "Not executed: the approval was not resolved in this turn.");
     } else {
         // Fallback to legacy in-memory state
         history = state.history.lock().map_err(|e| e.to_string())?.clone();
     }
 
-    // 3. Get Current Agent Mode
-    let agent_mode = state.mode.lock().unwrap().clone();
-
     // 4. Context & System Prompt
     let active_window = context::get_active_window_info().unwrap_or_else(|_| "Unknown".to_string());
     let cwd = working_dir.clone().unwrap_or_else(|| ".".to_string());
@@ -645,7 +1771,7 @@ CAPABILITIES & PERMISSIONS:
 - Search: You can search_files (grep) to find text, or find_file_smart to find files by name/path.
 
 SAFETY & CONFIRMATION:
-- Sensitive actions require approval. The system will emit an approval id; wait for the user to reply 'approve <id>' (or 'deny <id>').
+- Sensitive actions require approval. The system will emit an approval id; wait for the user to reply 'approve <id>' (or 'deny <id>', 'approve all').
 - Keep actions scoped to the working directory; out-of-scope paths need approval.
 - Respect read-only mode and do not attempt writes/exec when enabled.
 
@@ -697,220 +1823,183 @@ TOOLS:
     };
     history.push(Message { role: "user".into(), content: Some(MessageContent::Text(user_content)), tool_calls: None, tool_call_id: None });
 
-    // 4. Execution Loop
-    let client = Client::new();
-    let tools = vec![
-        json!({ "type": "function", "function": { "name": "set_plan", "description": "Create a visual plan", "parameters": { "type": "object", "properties": { "steps": { "type": "array", "items": { "type": "string" } } }, "required": ["steps"] } } }),
-        json!({ "type": "function", "function": { "name": "complete_step", "description": "Mark step complete", "parameters": { "type": "object", "properties": { "step_index": { "type": "integer" } }, "required": ["step_index"] } } }),
-        json!({ "type": "function", "function": { "name": "list_dir", "description": "List files", "parameters": { "type": "object", "properties": { "path": { "type": "string" } }, "required": ["path"] } } }),
-        json!({ "type": "function", "function": { "name": "read_file", "description": "Read file", "parameters": { "type": "object", "properties": { "path": { "type": "string" } }, "required": ["path"] } } }),
-        json!({ "type": "function", "function": { "name": "write_file", "description": "Write file", "parameters": { "type": "object", "properties": { "path": { "type": "string" }, "content": { "type": "string" } }, "required": ["path", "content"] } } }),
-        json!({ "type": "function", "function": { "name": "execute_command", "description": "Run command", "parameters": { "type": "object", "properties": { "command": { "type": "string" }, "args": { "type": "array", "items": { "type": "string" } } }, "required": ["command", "args"] } } }),
-        json!({ "type": "function", "function": { "name": "open_app", "description": "Open a file or app", "parameters": { "type": "object", "properties": { "path": { "type": "string" } }, "required": ["path"] } } }),
-        json!({ "type": "function", "function": { "name": "fetch_url", "description": "Fetch content from URL", "parameters": { "type": "object", "properties": { "url": { "type": "string" }, "expected_hash": { "type": "string", "description": "Optional SHA256 hash to verify content" } }, "required": ["url"] } } }),
-        json!({ "type": "function", "function": { "name": "get_system_stats", "description": "Get CPU/Memory usage", "parameters": { "type": "object", "properties": {}, "required": [] } } }),
-        json!({ "type": "function", "function": { "name": "search_files", "description": "Search text in files", "parameters": { "type": "object", "properties": { "query": { "type": "string" }, "path": { "type": "string" } }, "required": ["query", "path"] } } }),
-        json!({ "type": "function", "function": { "name": "search_web", "description": "Search web (opens browser)", "parameters": { "type": "object", "properties": { "query": { "type": "string" } }, "required": ["query"] } } }),
-        json!({ "type": "function", "function": { "name": "keyboard_type", "description": "Simulate typing text", "parameters": { "type": "object", "properties": { "text": { "type": "string" } }, "required": ["text"] } } }),
-        json!({ "type": "function", "function": { "name": "keyboard_press", "description": "Simulate key press (Enter, Tab, etc)", "parameters": { "type": "object", "properties": { "key": { "type": "string" } }, "required": ["key"] } } }),
-        json!({ "type": "function", "function": { "name": "mouse_move", "description": "Move mouse to coordinates", "parameters": { "type": "object", "properties": { "x": { "type": "integer" }, "y": { "type": "integer" } }, "required": ["x", "y"] } } }),
-        json!({ "type": "function", "function": { "name": "mouse_click", "description": "Click mouse button", "parameters": { "type": "object", "properties": { "button": { "type": "string", "enum": ["left", "right", "middle"] } }, "required": ["button"] } } }),
-        json!({ "type": "function", "function": { "name": "get_screenshot", "description": "Get current screen as base64 image", "parameters": { "type": "object", "properties": {}, "required": [] } } }),
-        json!({ "type": "function", "function": { "name": "wait", "description": "Wait for N milliseconds", "parameters": { "type": "object", "properties": { "milliseconds": { "type": "integer" } }, "required": ["milliseconds"] } } }),
-        json!({ "type": "function", "function": { "name": "create_docx", "description": "Create a Word DOCX file from text content", "parameters": { "type": "object", "properties": { "content": { "type": "string" }, "filename": { "type": "string" } }, "required": ["content", "filename"] } } }),
-        json!({ "type": "function", "function": { "name": "create_slide_deck", "description": "Create a Reveal.js slide deck (HTML) from text", "parameters": { "type": "object", "properties": { "content": { "type": "string" }, "filename": { "type": "string" } }, "required": ["content", "filename"] } } }),
-        json!({ "type": "function", "function": { "name": "find_file_smart", "description": "Recursively find files by name (fuzzy)", "parameters": { "type": "object", "properties": { "query": { "type": "string" }, "path": { "type": "string" } }, "required": ["query", "path"] } } })
-    ];
 
-    let mut final_response = String::new();
-    
-    'conversation: for _ in 0..10 { 
-        let request_body = json!({
-            "model": model,
-            "messages": history,
-            "tools": tools,
-            "tool_choice": "auto"
-        });
+    // 5. Execution Loop
+    let outcome = run_conversation(&ctx, &mut history, Vec::new())
+        .instrument(turn_span(&ctx))
+        .await?;
+    if let TurnOutcome::Unavailable(msg) = outcome {
+        return Ok(msg);
+    }
+    finish_turn(&ctx, &state, history, &outcome).await
+}
 
-        let api_start = std::time::Instant::now();
-        let mut last_err: Option<String> = None;
-        let mut body_opt: Option<OpenAIChatResponse> = None;
-        for attempt in 0..3 {
-            let res = client.post("https://api.openai.com/v1/chat/completions")
-                .header("Authorization", format!("Bearer {}", api_key))
-                .json(&request_body)
-                .send()
-                .await;
-
-            match res {
-                Ok(resp) => {
-                    if !resp.status().is_success() {
-                        last_err = Some(format!("API status {}", resp.status()));
-                    } else {
-                        match resp.json::<OpenAIChatResponse>().await {
-                            Ok(body) => {
-                                body_opt = Some(body);
-                                break;
-                            }
-                            Err(e) => last_err = Some(format!("Parse failed: {}", e)),
-                        }
-                    }
-                }
-                Err(e) => {
-                    last_err = Some(format!("Request failed: {}", e));
-                }
-            }
-            let backoff = 2u64.pow(attempt) * 300;
-            tokio::time::sleep(Duration::from_millis(backoff)).await;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let body = match body_opt {
-            Some(b) => b,
-            None => {
-                let msg = format!("Chat request failed: {}", last_err.unwrap_or_else(|| "unknown error".into()));
-                let _ = app.emit("telemetry", TelemetryEvent {
-                    tool: "openai_chat".into(),
-                    status: "error".into(),
-                    duration_ms: api_start.elapsed().as_millis(),
-                    kind: "api".into(),
-                });
-                return Ok(format!("Offline or unavailable. {}", msg));
-            }
-        };
-        let api_latency = api_start.elapsed().as_millis();
-        let _ = app.emit("telemetry", TelemetryEvent {
-            tool: "openai_chat".into(),
-            status: "success".into(),
-            duration_ms: api_latency,
-            kind: "api".into(),
-        });
-        let choice = body.choices.first().ok_or("No response")?;
-        let message = &choice.message;
+    fn workspace() -> (PathBuf, Option<String>) {
+        let dir = std::env::temp_dir().join(format!("deskwork-grants-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        let wd = Some(dir.to_string_lossy().to_string());
+        (dir, wd)
+    }
 
-        history.push(message.clone());
+    #[test]
+    fn grant_under_dir_stays_inside_it() {
+        let (dir, wd) = workspace();
+        let (tool, args, duration) = parse_grant_prompt("allow write_file under src/ for this session").unwrap();
+        assert_eq!(tool, "write_file");
+        assert_eq!(args["path"], "src/*");
+        assert_eq!(duration, None);
+
+        let state = ApprovalState::default();
+        add_grant_to_state(&state, tool, args, None, Some("s1".into()), duration);
+        let find = |path: &str| find_grant(&state, "write_file", &json!({"path": path}), &wd, Some("s1"));
+        assert!(find("src/main.rs").is_some());
+        assert!(find("src/../../etc/passwd").is_none());
+        assert!(find("src/../build.rs").is_none());
+        assert!(find(&format!("{}/src/../../x", dir.display())).is_none());
+        // Other sessions do not inherit it
+        assert!(find_grant(&state, "write_file", &json!({"path": "src/main.rs"}), &wd, Some("s2")).is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
 
-        if let Some(tool_calls) = &message.tool_calls {
-            for tool_call in tool_calls {
-                let function_name = &tool_call.function.name;
-                let args: Value = serde_json::from_str(&tool_call.function.arguments).unwrap_or(json!({}));
-                if !crate::skills::is_tool_enabled(&skill_state, function_name) {
-                    let msg = format!("Tool '{}' is disabled in Skills settings.", function_name);
-                    history.push(Message {
-                        role: "tool".into(),
-                        content: Some(MessageContent::Text(msg)),
-                        tool_calls: None,
-                        tool_call_id: Some(tool_call.id.clone()),
-                    });
-                    continue;
-                }
+    #[test]
+    fn grants_do_not_satisfy_safety_asks() {
+        let (dir, wd) = workspace();
+        let policy_state = PolicyState::default();
+        let ctx = |read_only| PolicyContext { mode: &AgentMode::Build, read_only, working_dir: &wd, session_id: Some("s1") };
 
-                // Check Agent Mode restrictions
-                if agent_mode == AgentMode::Plan {
-                    if matches!(function_name.as_str(), "write_file" | "execute_command" | "create_docx" | "create_slide_deck") {
-                        let msg = format!("Tool '{}' is disabled in PLAN mode. Switch to BUILD mode to execute.", function_name);
-                        history.push(Message {
-                            role: "tool".into(),
-                            content: Some(MessageContent::Text(msg)),
-                            tool_calls: None,
-                            tool_call_id: Some(tool_call.id.clone()),
-                        });
-                        continue;
-                    }
-                }
+        let ordinary = policy::evaluate(&policy_state, "write_file", &json!({"path": "src/a.rs"}), &ctx(false));
+        assert_eq!(ordinary.decision, Decision::Ask);
+        assert!(ordinary.grantable());
 
-                if let Some(reason) = approval_reason(function_name, &args, &working_dir, &settings) {
-                    let dry_run = match function_name.as_str() {
-                        "execute_command" => {
-                            let cmd = args["command"].as_str().unwrap_or("");
-                            let args_vec: Vec<String> = args["args"].as_array().map(|arr| arr.iter().map(|v| v.as_str().unwrap_or("").to_string()).collect()).unwrap_or_default();
-                            format!("Would run: {} {}", cmd, args_vec.join(" "))
-                        }
-                        "write_file" => {
-                            let path = args["path"].as_str().unwrap_or("");
-                            format!("Would write to {}", path)
-                        }
-                        _ => format!("Would run {}", function_name),
-                    };
-                    final_response = request_approval(
-                        &approval_state,
-                        &app,
-                        function_name,
-                        dry_run,
-                        args.clone(),
-                        working_dir.clone(),
-                        reason,
-                        active_session_id.clone(),
-                    );
-                    history.push(Message {
-                        role: "assistant".into(),
-                        content: Some(MessageContent::Text(final_response.clone())),
-                        tool_calls: None,
-                        tool_call_id: None,
-                    });
-                    break 'conversation;
-                }
+        let read_only = policy::evaluate(&policy_state, "write_file", &json!({"path": "src/a.rs"}), &ctx(true));
+        assert_eq!(read_only.decision, Decision::Ask);
+        assert!(!read_only.grantable());
 
-                let id = uuid::Uuid::new_v4().to_string();
-                let tool_output = dispatch_tool(&app, function_name, &args, &working_dir, id.clone(), settings.structured_logs, &audit_state, active_session_id.as_ref()).await;
+        let outside = policy::evaluate(&policy_state, "write_file", &json!({"path": "../a.rs"}), &ctx(false));
+        assert_eq!(outside.decision, Decision::Ask);
+        assert!(!outside.grantable());
+        let _ = std::fs::remove_dir_all(dir);
+    }
 
-                history.push(Message {
-                    role: "tool".into(),
-                    content: Some(tool_output.unwrap_or_else(|e| MessageContent::Text(format!("Error: {}", e)))),
-                    tool_calls: None,
-                    tool_call_id: Some(tool_call.id.clone()),
-                });
-            }
-            continue;
-        } else {
-             // Handle response content which might be just text or null
-            if let Some(content) = &message.content {
-                match content {
-                    MessageContent::Text(t) => {
-                        final_response = t.clone();
-                        // Log assistant response
-                        if let Some(sid) = &active_session_id {
-                            logging::log(&app, sid, "ASSISTANT", &t);
-                        }
-                    },
-                    MessageContent::Parts(parts) => {
-                        // Concatenate text parts for simple string return
-                        final_response = parts.iter().filter_map(|p| p.text.clone()).collect::<Vec<_>>().join("\n");
-                        // Log assistant response
-                        if let Some(sid) = &active_session_id {
-                            logging::log(&app, sid, "ASSISTANT", &final_response);
-                        }
-                    }
-                }
-            }
-            break;
-        }
+    #[test]
+    fn write_preview_reads_only_workspace_files() {
+        let (dir, wd) = workspace();
+        std::fs::write(dir.join("src/a.txt"), "old line\n").unwrap();
+        let (outside, _) = workspace();
+        std::fs::write(outside.join("secret.txt"), "top secret\n").unwrap();
+        let preview = |path: &str| render_preview("write_file", &json!({"path": path, "content": "new line\n"}), &wd);
+
+        // Relative paths are diffed against the workspace, not the process CWD
+        assert!(preview("src/a.txt").contains("-old line"));
+        let leaked = preview(&outside.join("secret.txt").to_string_lossy());
+        assert!(!leaked.contains("top secret"));
+        assert!(leaked.contains("+new line"));
+        let _ = std::fs::remove_dir_all(dir);
+        let _ = std::fs::remove_dir_all(outside);
     }
 
-    // Stream final response as tokens
-    if !final_response.is_empty() {
-        let chars: Vec<char> = final_response.chars().collect();
-        for chunk in chars.chunks(5) {
-            let token: String = chunk.iter().collect();
-            let _ = app.emit("chat_stream", json!({"token": token, "done": false}));
-            tokio::time::sleep(Duration::from_millis(10)).await;
-        }
-        let _ = app.emit("chat_stream", json!({"done": true}));
+    #[test]
+    fn remembered_command_grant_needs_the_same_arguments() {
+        let state = ApprovalState::default();
+        let approved = json!({"command": "rm", "args": ["-rf", "build"]});
+        let patterns = remembered_patterns("execute_command", &approved);
+        add_grant_to_state(&state, "execute_command".into(), patterns, Some(approved.clone()), Some("s1".into()), None);
+        let find = |args: &Value| find_grant(&state, "execute_command", args, &None, Some("s1"));
+        assert!(find(&approved).is_some());
+        assert!(find(&json!({"command": "rm", "args": ["-rf", "/"]})).is_none());
+        assert!(find(&json!({"command": "rm", "args": ["-rf", "build", "src"]})).is_none());
     }
 
-    if let Some(sid) = active_session_id {
-        let sanitized = sanitize_history_for_storage(&history);
-        let session = Session {
-            id: sid.clone(),
-            title: "Session".to_string(),
-            messages: sanitized,
-            created_at: 0,
-            updated_at: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs(),
-            pinned: false,
+    #[test]
+    fn persisted_queue_is_redacted() {
+        let path = std::env::temp_dir().join(format!("deskwork-queue-{}.json", uuid::Uuid::new_v4()));
+        let state = ApprovalState { path: Mutex::new(Some(path.clone())), ..Default::default() };
+        let secret = "sk-proj-abcdefghijklmnopqrstuvwxyz0123456789";
+        let pending = PendingApproval {
+            id: "a1".into(),
+            function_name: "write_file".into(),
+            action: "Would write to .env".into(),
+            args: json!({"path": ".env", "content": format!("OPENAI_API_KEY={}", secret)}),
+            working_dir: None,
+            expires_at: now_ts() + 60,
+            session_id: None,
+            reason: String::new(),
+            risk: "medium".into(),
+            preview: format!("+OPENAI_API_KEY={}", secret),
+            tool_call_id: None,
+            args_redacted: false,
         };
-        save_session_to_disk(&session).ok();
-    } else {
-        *state.history.lock().map_err(|e| e.to_string())? = history;
+        persist_queue(&state, std::slice::from_ref(&pending));
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(!written.contains(secret));
+        assert!(!path.with_extension("json.tmp").exists());
+        let saved: Vec<PendingApproval> = serde_json::from_str(&written).unwrap();
+        assert!(saved[0].args_redacted);
+        assert_eq!(saved[0].args["path"], ".env");
+        let _ = std::fs::remove_file(path);
     }
 
-    Ok(final_response)
+    fn assistant_calling(ids: &[&str]) -> Message {
+        Message {
+            role: "assistant".into(),
+            content: None,
+            tool_calls: Some(ids.iter().map(|id| ToolCall {
+                id: id.to_string(),
+                r#type: "function".into(),
+                function: FunctionCall { name: "write_file".into(), arguments: "{}".into() },
+            }).collect()),
+            tool_call_id: None,
+        }
+    }
+
+    fn note(text: &str) -> Message {
+        Message { role: "assistant".into(), content: Some(MessageContent::Text(text.into())), tool_calls: None, tool_call_id: None }
+    }
+
+    #[test]
+    fn open_tool_calls_are_answered_in_place() {
+        let mut messages = vec![
+            assistant_calling(&["a", "b", "c"]),
+            tool_reply("a", MessageContent::Text("done".into())),
+            note("Approval required"),
+        ];
+        answer_open_tool_calls(&mut messages, Some(("b", "denied")), "not run");
+        let roles: Vec<(&str, Option<&str>)> = messages.iter().map(|m| (m.role.as_str(), m.tool_call_id.as_deref())).collect();
+        assert_eq!(roles, vec![
+            ("assistant", None),
+            ("tool", Some("a")),
+            ("tool", Some("b")),
+            ("tool", Some("c")),
+            ("assistant", None),
+        ]);
+        assert!(matches!(&messages[2].content, Some(MessageContent::Text(t)) if t == "denied"));
+        assert!(matches!(&messages[3].content, Some(MessageContent::Text(t)) if t == "not run"));
+
+        // Already complete histories are left alone
+        let before = messages.len();
+        answer_open_tool_calls(&mut messages, None, "not run");
+        assert_eq!(messages.len(), before);
+    }
+
+    #[test]
+    fn resumed_call_result_is_saved_when_the_model_is_unavailable() {
+        crate::session_manager::use_test_store();
+        let sid = uuid::Uuid::new_v4().to_string();
+        // As stored at the approval pause
+        let paused = Session { id: sid.clone(), messages: vec![assistant_calling(&["a"]), note("Approval required")], ..Default::default() };
+        save_session_to_disk(&paused).unwrap();
+
+        // Approved and run, then the follow-up request failed
+        let resumed = vec![assistant_calling(&["a"]), tool_reply("a", MessageContent::Text("written".into()))];
+        assert!(store_transcript(&sid, resumed, |_| {}).is_some());
+
+        let mut next_turn = crate::session_manager::load_session(&sid).unwrap().messages;
+        answer_open_tool_calls(&mut next_turn, None, "Not executed: the approval was not resolved in this turn.");
+        assert_eq!(next_turn.len(), 2);
+        assert!(matches!(&next_turn[1].content, Some(MessageContent::Text(t)) if t == "written"));
+    }
 }
@@ -1,14 +1,31 @@
 use sha2::{Digest, Sha256};
 use serde::{Deserialize, Serialize};
 use std::fs::{self, OpenOptions};
-use std::io::{BufRead, BufReader, Write};
-use std::path::PathBuf;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
-use tauri::{State, Manager};
-use std::sync::Mutex;
+use tauri::{Emitter, State, Manager};
+use std::sync::{mpsc, Mutex};
+use serde_json::json;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use keyring::Entry;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use crate::settings::{SettingsState, KEYRING_SERVICE};
+
+const KEYRING_AUDIT_KEY_USER: &str = "audit_signing_key";
+const ACTIVE_LOG: &str = "audit.jsonl";
+const SEGMENTS_DIR: &str = "audit";
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AuditEntry {
+    // Entries are hash-chained and never rewritten, so there are no migrations: readers accept
+    // every version up to the current one. 0 is an entry written before versioning.
+    #[serde(default, skip_serializing_if = "is_unversioned")]
+    pub schema_version: u32,
     pub ts: u64,
     pub tool: String,
     pub status: String,
@@ -17,11 +34,32 @@ pub struct AuditEntry {
     pub working_dir: Option<String>,
     pub prev_hash: String,
     pub hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    // How the call was authorized, e.g. "policy:builtin", "grant:<id>", "approval:<id>"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approval: Option<String>,
+    // Hex Ed25519 signature over `hash`, made with this install's audit key
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub sig: String,
+}
+
+pub const SCHEMA_VERSION: u32 = 2;
+// Entries from this version on are signed whenever the install has a key, so a verifier holding
+// the public key rejects them unsigned.
+pub const SIGNED_SCHEMA_VERSION: u32 = 2;
+
+fn is_unversioned(version: &u32) -> bool {
+    *version == 0
 }
 
 #[derive(Default)]
 pub struct AuditState {
     pub log_path: Mutex<Option<PathBuf>>,
+    pub signing_key: Mutex<Option<SigningKey>>,
+    // Hash of the last entry handed to the writer; the next entry chains onto it
+    pub head: Mutex<String>,
+    pub writer: Mutex<Option<mpsc::Sender<AuditEntry>>>,
 }
 
 pub fn init(app_handle: &tauri::AppHandle) -> AuditState {
@@ -29,25 +67,386 @@ pub fn init(app_handle: &tauri::AppHandle) -> AuditState {
     if !config_dir.exists() {
         let _ = fs::create_dir_all(&config_dir);
     }
-    let log_path = config_dir.join("audit.jsonl");
+    let log_path = config_dir.join(ACTIVE_LOG);
+    // Without a keyring (e.g. headless Linux) entries are still hash-chained, just unsigned
+    let signing_key = match load_or_create_signing_key() {
+        Ok(key) => Some(key),
+        Err(e) => {
+            tracing::warn!("audit: entries will not be signed: {}", e);
+            None
+        }
+    };
     AuditState {
+        head: Mutex::new(read_last_hash(&log_path)),
+        writer: Mutex::new(Some(spawn_writer(app_handle.clone(), log_path.clone(), signing_key.clone()))),
         log_path: Mutex::new(Some(log_path)),
+        signing_key: Mutex::new(signing_key),
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim();
+    if !text.len().is_multiple_of(2) {
+        return None;
     }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
 }
 
-fn read_last_hash(path: &PathBuf) -> String {
-    if !path.exists() {
-        return String::new();
+// Per-install Ed25519 key, kept in the OS keyring next to the API key. A new key is only made
+// when there is none; any other keyring error is returned so a stored key is never replaced.
+fn load_or_create_signing_key() -> Result<SigningKey, String> {
+    let entry = Entry::new(KEYRING_SERVICE, KEYRING_AUDIT_KEY_USER).map_err(|e| e.to_string())?;
+    match entry.get_password() {
+        Ok(stored) => {
+            let bytes: [u8; 32] = from_hex(&stored)
+                .and_then(|b| b.try_into().ok())
+                .ok_or("Stored audit signing key is malformed")?;
+            return Ok(SigningKey::from_bytes(&bytes));
+        }
+        Err(keyring::Error::NoEntry) => {}
+        Err(e) => return Err(e.to_string()),
     }
-    if let Ok(file) = fs::File::open(path) {
-        let reader = BufReader::new(file);
-        if let Some(Ok(line)) = reader.lines().last() {
-            if let Ok(entry) = serde_json::from_str::<AuditEntry>(&line) {
-                return entry.hash;
+    let mut seed = [0u8; 32];
+    OsRng.fill_bytes(&mut seed);
+    entry.set_password(&to_hex(&seed)).map_err(|e| e.to_string())?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+pub fn parse_public_key(hex_key: &str) -> Result<VerifyingKey, String> {
+    let bytes: [u8; 32] = from_hex(hex_key)
+        .and_then(|b| b.try_into().ok())
+        .ok_or("Public key must be 64 hex characters")?;
+    VerifyingKey::from_bytes(&bytes).map_err(|e| e.to_string())
+}
+
+fn signature_valid(key: &VerifyingKey, hash: &str, sig: &str) -> bool {
+    let Some(bytes) = from_hex(sig).and_then(|b| <[u8; 64]>::try_from(b).ok()) else {
+        return false;
+    };
+    key.verify(hash.as_bytes(), &Signature::from_bytes(&bytes)).is_ok()
+}
+
+// First line of every segment. `prev_hash` is the final hash of the segment before it, so the
+// chain carries on across rotated files. Logs written before rotation existed have no header.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SegmentHeader {
+    pub segment_header: u32,
+    pub created: u64,
+    pub prev_hash: String,
+    // Entry schema version from which the install signs everything (SIGNED_SCHEMA_VERSION);
+    // 0 when the segment was started without a signing key. Hashed and signed with the header,
+    // so signatures cannot be stripped from a segment and the header quietly downgraded.
+    #[serde(default, skip_serializing_if = "is_unversioned")]
+    pub signed_from: u32,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub hash: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub sig: String,
+}
+
+impl SegmentHeader {
+    fn new(created: u64, prev_hash: String, signing_key: Option<&SigningKey>) -> Self {
+        let mut header = SegmentHeader {
+            segment_header: 1,
+            created,
+            prev_hash,
+            signed_from: if signing_key.is_some() { SIGNED_SCHEMA_VERSION } else { 0 },
+            hash: String::new(),
+            sig: String::new(),
+        };
+        header.hash = compute_header_hash(&header);
+        if let Some(key) = signing_key {
+            header.sig = to_hex(&key.sign(header.hash.as_bytes()).to_bytes());
+        }
+        header
+    }
+}
+
+fn compute_header_hash(header: &SegmentHeader) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(b"segment_header:");
+    hasher.update(header.segment_header.to_string().as_bytes());
+    hasher.update(header.created.to_string().as_bytes());
+    hasher.update(header.prev_hash.as_bytes());
+    hasher.update(b"signed_from:");
+    hasher.update(header.signed_from.to_string().as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+// A rotated segment under `<config>/audit/`, named `audit-<started>-<ended>.jsonl[.gz]`.
+#[derive(Debug, Clone)]
+pub struct SegmentFile {
+    pub path: PathBuf,
+    pub started: u64,
+    pub ended: u64,
+}
+
+fn segments_dir(active: &Path) -> PathBuf {
+    active.with_file_name(SEGMENTS_DIR)
+}
+
+// Rotated segments, oldest first. A plain file wins over a `.gz` twin left by an interrupted compression.
+pub fn list_segments(active: &Path) -> Vec<SegmentFile> {
+    let Ok(read_dir) = fs::read_dir(segments_dir(active)) else {
+        return Vec::new();
+    };
+    let mut segments: Vec<(SegmentFile, bool)> = read_dir
+        .flatten()
+        .filter_map(|dir_entry| {
+            let path = dir_entry.path();
+            let name = path.file_name()?.to_str()?.to_string();
+            let compressed = name.ends_with(".jsonl.gz");
+            if !compressed && !name.ends_with(".jsonl") {
+                return None;
             }
+            let stem = name.strip_prefix("audit-")?.split('.').next()?;
+            let (started, ended) = stem.split_once('-')?;
+            Some((
+                SegmentFile { started: started.parse().ok()?, ended: ended.parse().ok()?, path },
+                compressed,
+            ))
+        })
+        .collect();
+    segments.sort_by_key(|(s, compressed)| (s.started, s.ended, *compressed));
+    segments.dedup_by(|a, b| a.0.started == b.0.started && a.0.ended == b.0.ended);
+    segments.into_iter().map(|(s, _)| s).collect()
+}
+
+pub fn read_segment(path: &Path) -> Result<String, String> {
+    let mut content = String::new();
+    if path.extension().is_some_and(|ext| ext == "gz") {
+        let file = fs::File::open(path).map_err(|e| e.to_string())?;
+        GzDecoder::new(file).read_to_string(&mut content).map_err(|e| e.to_string())?;
+    } else {
+        content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    }
+    Ok(content)
+}
+
+// Rotated segments followed by the active log, oldest first.
+pub fn chain_files(active: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = list_segments(active).into_iter().map(|s| s.path).collect();
+    if active.exists() {
+        files.push(active.to_path_buf());
+    }
+    files
+}
+
+// Hash the next entry should chain onto if `line` were the last line of the log.
+fn chain_hash_of_line(line: &[u8]) -> Option<String> {
+    if let Ok(entry) = serde_json::from_slice::<AuditEntry>(line) {
+        return Some(entry.hash);
+    }
+    serde_json::from_slice::<SegmentHeader>(line).ok().map(|header| header.prev_hash)
+}
+
+// Reads only the tail of the file; used at startup to seed the chain head.
+fn read_last_line(path: &Path) -> Option<Vec<u8>> {
+    let mut file = fs::File::open(path).ok()?;
+    let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let mut window: u64 = 8 * 1024;
+    loop {
+        let start = len.saturating_sub(window);
+        let mut buf = Vec::new();
+        file.seek(SeekFrom::Start(start)).ok()?;
+        file.read_to_end(&mut buf).ok()?;
+        let trimmed = buf.trim_ascii_end();
+        return match trimmed.iter().rposition(|&b| b == b'\n') {
+            Some(pos) => Some(trimmed[pos + 1..].to_vec()),
+            None if start == 0 => Some(trimmed.to_vec()),
+            None => {
+                window *= 4;
+                continue;
+            }
+        };
+    }
+}
+
+fn read_last_hash(active: &Path) -> String {
+    if active.exists() {
+        return read_last_line(active)
+            .and_then(|line| chain_hash_of_line(&line))
+            .unwrap_or_default();
+    }
+    // No active file (e.g. rotation was interrupted): continue from the newest segment
+    list_segments(active)
+        .last()
+        .and_then(|segment| read_segment(&segment.path).ok())
+        .and_then(|content| {
+            content
+                .lines()
+                .rev()
+                .find(|line| !line.trim().is_empty())
+                .and_then(|line| chain_hash_of_line(line.as_bytes()))
+        })
+        .unwrap_or_default()
+}
+
+struct RotationPolicy {
+    max_bytes: u64,
+    daily: bool,
+    compress: bool,
+    retention_days: u64,
+}
+
+fn rotation_policy(app: &tauri::AppHandle) -> RotationPolicy {
+    let settings = app
+        .try_state::<SettingsState>()
+        .map(|state| state.0.lock().unwrap_or_else(|e| e.into_inner()).clone())
+        .unwrap_or_default();
+    RotationPolicy {
+        max_bytes: settings.audit_max_segment_mb.saturating_mul(1024 * 1024),
+        daily: settings.audit_rotate_daily,
+        compress: settings.audit_compress_segments,
+        retention_days: settings.audit_retention_days,
+    }
+}
+
+struct ActiveSegment {
+    file: fs::File,
+    started: u64,
+    size: u64,
+    has_entries: bool,
+}
+
+impl ActiveSegment {
+    fn due_for_rotation(&self, policy: &RotationPolicy, now: u64) -> bool {
+        self.has_entries
+            && ((policy.max_bytes > 0 && self.size >= policy.max_bytes)
+                || (policy.daily && self.started / 86_400 != now / 86_400))
+    }
+}
+
+// Opens the active log, starting a new segment (header first) if it is missing or empty.
+fn open_segment(path: &Path, next: &AuditEntry, signing_key: Option<&SigningKey>) -> Result<ActiveSegment, String> {
+    let mut file = OpenOptions::new().create(true).append(true).read(true).open(path).map_err(|e| e.to_string())?;
+    let size = file.metadata().map_err(|e| e.to_string())?.len();
+    if size == 0 {
+        let header = SegmentHeader::new(next.ts, next.prev_hash.clone(), signing_key);
+        let line = format!("{}\n", serde_json::to_string(&header).map_err(|e| e.to_string())?);
+        file.write_all(line.as_bytes()).map_err(|e| e.to_string())?;
+        file.sync_data().map_err(|e| e.to_string())?;
+        return Ok(ActiveSegment { file, started: next.ts, size: line.len() as u64, has_entries: false });
+    }
+
+    let mut first_line = String::new();
+    let _ = BufReader::new(&file).read_line(&mut first_line);
+    let started = serde_json::from_str::<SegmentHeader>(&first_line)
+        .map(|header| header.created)
+        .or_else(|_| serde_json::from_str::<AuditEntry>(&first_line).map(|entry| entry.ts))
+        .unwrap_or(next.ts);
+    let has_entries = read_last_line(path).is_some_and(|line| serde_json::from_slice::<AuditEntry>(&line).is_ok());
+    Ok(ActiveSegment { file, started, size, has_entries })
+}
+
+fn compress_segment(path: &Path) -> Result<(), String> {
+    let gz_path = PathBuf::from(format!("{}.gz", path.display()));
+    let tmp_path = PathBuf::from(format!("{}.gz.tmp", path.display()));
+    let mut input = fs::File::open(path).map_err(|e| e.to_string())?;
+    let output = fs::File::create(&tmp_path).map_err(|e| e.to_string())?;
+    let mut encoder = GzEncoder::new(output, Compression::default());
+    std::io::copy(&mut input, &mut encoder).map_err(|e| e.to_string())?;
+    encoder.finish().and_then(|f| f.sync_all()).map_err(|e| e.to_string())?;
+    fs::rename(&tmp_path, &gz_path).map_err(|e| e.to_string())?;
+    fs::remove_file(path).map_err(|e| e.to_string())
+}
+
+fn prune_segments(active: &Path, retention_days: u64, now: u64) -> Result<(), String> {
+    if retention_days == 0 {
+        return Ok(());
+    }
+    let cutoff = now.saturating_sub(retention_days * 86_400);
+    for segment in list_segments(active) {
+        if segment.ended < cutoff {
+            fs::remove_file(&segment.path).map_err(|e| e.to_string())?;
         }
     }
-    String::new()
+    Ok(())
+}
+
+// Moves the active log into the segments dir. The next write starts a fresh active file
+// whose header links back to the last hash of this one.
+fn rotate_segment(path: &Path, started: u64, now: u64, policy: &RotationPolicy) -> Result<(), String> {
+    let dir = segments_dir(path);
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let target = dir.join(format!("audit-{}-{}.jsonl", started, now));
+    fs::rename(path, &target).map_err(|e| e.to_string())?;
+    if policy.compress {
+        compress_segment(&target)?;
+    }
+    prune_segments(path, policy.retention_days, now)
+}
+
+fn write_entry(
+    segment: &mut Option<ActiveSegment>,
+    path: &Path,
+    entry: &AuditEntry,
+    signing_key: Option<&SigningKey>,
+) -> Result<(), String> {
+    if segment.is_none() {
+        *segment = Some(open_segment(path, entry, signing_key)?);
+    }
+    let active = segment.as_mut().ok_or("Audit log is not open")?;
+    let line = format!("{}\n", serde_json::to_string(entry).map_err(|e| e.to_string())?);
+    active.file.write_all(line.as_bytes()).map_err(|e| e.to_string())?;
+    active.file.sync_data().map_err(|e| e.to_string())?;
+    active.size += line.len() as u64;
+    active.has_entries = true;
+    Ok(())
+}
+
+pub(crate) fn report_audit_failure(app: &tauri::AppHandle, error: String) {
+    tracing::error!("audit: {}", error);
+    let _ = app.emit("telemetry", json!({
+        "tool": "audit_log",
+        "status": "error",
+        "duration_ms": 0,
+        "kind": "audit",
+        "error": error,
+    }));
+}
+
+// Single writer thread: entries are written in the order they were chained and fsynced one by one.
+// Rotation happens here too, between entries, so no entry is ever split across segments.
+fn spawn_writer(app: tauri::AppHandle, path: PathBuf, signing_key: Option<SigningKey>) -> mpsc::Sender<AuditEntry> {
+    let (tx, rx) = mpsc::channel::<AuditEntry>();
+    std::thread::spawn(move || {
+        let mut segment: Option<ActiveSegment> = None;
+        for entry in rx {
+            let policy = rotation_policy(&app);
+            if segment.is_none() && path.exists() {
+                segment = open_segment(&path, &entry, signing_key.as_ref()).ok();
+            }
+            if segment.as_ref().is_some_and(|s| s.due_for_rotation(&policy, entry.ts)) {
+                let started = segment.take().map(|s| s.started).unwrap_or(entry.ts);
+                if let Err(e) = rotate_segment(&path, started, entry.ts, &policy) {
+                    report_audit_failure(&app, format!("Audit log rotation failed: {}", e));
+                }
+            }
+
+            let mut result = Ok(());
+            // Retry logic for file access
+            for _ in 0..3 {
+                result = write_entry(&mut segment, &path, &entry, signing_key.as_ref());
+                if result.is_ok() {
+                    break;
+                }
+                segment = None;
+                std::thread::sleep(std::time::Duration::from_millis(50));
+            }
+            if let Err(e) = result {
+                report_audit_failure(&app, format!("Audit entry for '{}' was not written: {}", entry.tool, e));
+            }
+        }
+    });
+    tx
 }
 
 fn now_ts() -> u64 {
@@ -57,80 +456,892 @@ fn now_ts() -> u64 {
         .as_secs()
 }
 
-fn compute_hash(prev_hash: &str, tool: &str, status: &str, action: &str, duration_ms: u128, working_dir: &Option<String>, ts: u64) -> String {
+// Fields added after the first release are only hashed when present, so older entries still verify.
+fn compute_hash(entry: &AuditEntry) -> String {
     let mut hasher = Sha256::new();
-    hasher.update(prev_hash.as_bytes());
-    hasher.update(tool.as_bytes());
-    hasher.update(status.as_bytes());
-    hasher.update(action.as_bytes());
-    hasher.update(duration_ms.to_string().as_bytes());
-    if let Some(wd) = working_dir {
+    hasher.update(entry.prev_hash.as_bytes());
+    hasher.update(entry.tool.as_bytes());
+    hasher.update(entry.status.as_bytes());
+    hasher.update(entry.action.as_bytes());
+    hasher.update(entry.duration_ms.to_string().as_bytes());
+    if let Some(wd) = &entry.working_dir {
         hasher.update(wd.as_bytes());
     }
-    hasher.update(ts.to_string().as_bytes());
+    hasher.update(entry.ts.to_string().as_bytes());
+    if let Some(sid) = &entry.session_id {
+        hasher.update(b"session_id:");
+        hasher.update(sid.as_bytes());
+    }
+    if let Some(approval) = &entry.approval {
+        hasher.update(b"approval:");
+        hasher.update(approval.as_bytes());
+    }
+    if entry.schema_version > 0 {
+        hasher.update(b"schema_version:");
+        hasher.update(entry.schema_version.to_string().as_bytes());
+    }
     format!("{:x}", hasher.finalize())
 }
 
+// What an audited call ran on behalf of.
+#[derive(Debug, Clone, Default)]
+pub struct AuditOrigin {
+    pub working_dir: Option<String>,
+    pub session_id: Option<String>,
+    pub approval: Option<String>,
+}
+
 pub fn append_audit(
     tool: &str,
     status: &str,
     action: &str,
     duration_ms: u128,
-    working_dir: Option<String>,
-    _structured: bool,
+    origin: AuditOrigin,
     state: &State<'_, AuditState>,
 ) -> Result<(), String> {
-    let path_guard = state.log_path.lock().unwrap();
-    let path = match &*path_guard {
-        Some(p) => p,
-        None => return Err("Audit log path not initialized".to_string()),
-    };
-
-    let prev_hash = read_last_hash(path);
-    let ts = now_ts();
-    let hash = compute_hash(&prev_hash, tool, status, action, duration_ms, &working_dir, ts);
-    
-    let entry = AuditEntry {
-        ts,
+    // Holding the head lock until the entry is queued keeps chain order == write order
+    let mut head = state.head.lock().unwrap();
+    let mut entry = AuditEntry {
+        schema_version: SCHEMA_VERSION,
+        ts: now_ts(),
         tool: tool.to_string(),
         status: status.to_string(),
         action: action.to_string(),
         duration_ms,
-        working_dir,
-        prev_hash,
-        hash,
+        working_dir: origin.working_dir,
+        prev_hash: head.clone(),
+        hash: String::new(),
+        session_id: origin.session_id,
+        approval: origin.approval,
+        sig: String::new(),
     };
-
-    let line = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
-    
-    // Retry logic for file access
-    for _ in 0..3 {
-        if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
-            if writeln!(file, "{}", line).is_ok() {
-                return Ok(());
-            }
-        }
-        std::thread::sleep(std::time::Duration::from_millis(50));
+    entry.hash = compute_hash(&entry);
+    if let Some(key) = &*state.signing_key.lock().unwrap() {
+        entry.sig = to_hex(&key.sign(entry.hash.as_bytes()).to_bytes());
     }
 
+    let hash = entry.hash.clone();
+    let writer = state.writer.lock().unwrap();
+    let tx = writer.as_ref().ok_or("Audit log writer not initialized")?;
+    tx.send(entry).map_err(|_| "Audit log writer has stopped".to_string())?;
+    *head = hash;
     Ok(())
 }
 
 #[tauri::command]
 pub fn get_audit_log(state: State<'_, AuditState>) -> Result<Vec<AuditEntry>, String> {
     let path_guard = state.log_path.lock().unwrap();
-    if let Some(path) = &*path_guard {
-        if !path.exists() {
-            return Ok(Vec::new());
-        }
-        let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
-        let entries: Vec<AuditEntry> = content
+    let Some(path) = &*path_guard else {
+        return Ok(Vec::new());
+    };
+    // Return last 100 entries reversed, reaching into rotated segments if the active log is short
+    let mut entries: Vec<AuditEntry> = Vec::new();
+    for file in chain_files(path).iter().rev() {
+        let content = read_segment(file)?;
+        let mut newer: Vec<AuditEntry> = content
             .lines()
             .filter_map(|line| serde_json::from_str(line).ok())
             .collect();
-        // Return last 100 entries reversed
-        Ok(entries.into_iter().rev().take(100).collect())
+        newer.reverse();
+        entries.extend(newer);
+        if entries.len() >= 100 {
+            break;
+        }
+    }
+    entries.truncate(100);
+    Ok(entries)
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct AuditQuery {
+    // Policy-style pattern, e.g. "write_file|execute_command" or "!get_*"
+    #[serde(default)]
+    pub tool: Option<String>,
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub since: Option<u64>,
+    #[serde(default)]
+    pub until: Option<u64>,
+    // Matches the entry's working dir and anything below it
+    #[serde(default)]
+    pub working_dir: Option<String>,
+    #[serde(default)]
+    pub session_id: Option<String>,
+    // Case-insensitive substring of the recorded action
+    #[serde(default)]
+    pub text: Option<String>,
+    // `next_cursor` from the previous page
+    #[serde(default)]
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct AuditPage {
+    pub entries: Vec<AuditEntry>,
+    pub next_cursor: Option<String>,
+}
+
+const DEFAULT_QUERY_LIMIT: usize = 100;
+const MAX_QUERY_LIMIT: usize = 1000;
+
+impl AuditQuery {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        if let Some(tool) = self.tool.as_deref().filter(|t| !t.is_empty()) {
+            if !crate::policy::pattern_matches(tool, std::slice::from_ref(&entry.tool)) {
+                return false;
+            }
+        }
+        if let Some(status) = self.status.as_deref().filter(|s| !s.is_empty()) {
+            if entry.status != status {
+                return false;
+            }
+        }
+        if self.since.is_some_and(|since| entry.ts < since) || self.until.is_some_and(|until| entry.ts > until) {
+            return false;
+        }
+        if let Some(dir) = self.working_dir.as_deref().filter(|d| !d.is_empty()) {
+            let dir = dir.trim_end_matches(['/', '\\']);
+            let under = entry.working_dir.as_deref().is_some_and(|wd| {
+                wd == dir || wd.strip_prefix(dir).is_some_and(|rest| rest.starts_with(['/', '\\']))
+            });
+            if !under {
+                return false;
+            }
+        }
+        if let Some(sid) = self.session_id.as_deref().filter(|s| !s.is_empty()) {
+            if entry.session_id.as_deref() != Some(sid) {
+                return false;
+            }
+        }
+        if let Some(text) = self.text.as_deref().filter(|t| !t.is_empty()) {
+            if !entry.action.to_lowercase().contains(&text.to_lowercase()) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// Walks the whole chain newest first, calling `visit` for each matching entry until it returns false.
+fn scan_newest_first(active: &Path, query: &AuditQuery, mut visit: impl FnMut(AuditEntry) -> bool) -> Result<(), String> {
+    // The cursor is the hash of the last entry on the previous page
+    let mut skipping = query.cursor.as_deref().filter(|c| !c.is_empty());
+    for file in chain_files(active).iter().rev() {
+        let content = read_segment(file)?;
+        for line in content.lines().rev() {
+            let Ok(entry) = serde_json::from_str::<AuditEntry>(line) else {
+                continue;
+            };
+            if let Some(cursor) = skipping {
+                if entry.hash == cursor {
+                    skipping = None;
+                }
+                continue;
+            }
+            if query.matches(&entry) && !visit(entry) {
+                return Ok(());
+            }
+        }
+    }
+    match skipping {
+        // Rotated out by retention, or never issued by this log
+        Some(cursor) => Err(format!("Audit cursor {} is not in the log; start again from the first page", cursor)),
+        None => Ok(()),
+    }
+}
+
+// Every audited action of one session, oldest first.
+pub fn session_entries(state: &AuditState, session_id: &str) -> Result<Vec<AuditEntry>, String> {
+    let path_guard = state.log_path.lock().map_err(|e| e.to_string())?;
+    let Some(path) = &*path_guard else {
+        return Err("Audit log path not initialized".to_string());
+    };
+    let query = AuditQuery { session_id: Some(session_id.to_string()), ..Default::default() };
+    let mut entries = Vec::new();
+    scan_newest_first(path, &query, |entry| {
+        entries.push(entry);
+        true
+    })?;
+    entries.reverse();
+    Ok(entries)
+}
+
+#[tauri::command]
+pub fn query_audit_log(query: AuditQuery, state: State<'_, AuditState>) -> Result<AuditPage, String> {
+    let path_guard = state.log_path.lock().unwrap();
+    let Some(path) = &*path_guard else {
+        return Err("Audit log path not initialized".to_string());
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_QUERY_LIMIT).clamp(1, MAX_QUERY_LIMIT);
+    let mut entries = Vec::new();
+    let mut has_more = false;
+    scan_newest_first(path, &query, |entry| {
+        if entries.len() == limit {
+            has_more = true;
+            return false;
+        }
+        entries.push(entry);
+        true
+    })?;
+    let next_cursor = if has_more { entries.last().map(|e| e.hash.clone()) } else { None };
+    Ok(AuditPage { entries, next_cursor })
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// Exports every entry matching the filters (cursor and limit are ignored), newest first.
+// `format` is "jsonl" or "csv"; the frontend writes the returned text to the chosen file.
+#[tauri::command]
+pub fn export_audit_log(query: AuditQuery, format: String, state: State<'_, AuditState>) -> Result<String, String> {
+    let path_guard = state.log_path.lock().unwrap();
+    let Some(path) = &*path_guard else {
+        return Err("Audit log path not initialized".to_string());
+    };
+    let query = AuditQuery { cursor: None, limit: None, ..query };
+    let mut entries = Vec::new();
+    scan_newest_first(path, &query, |entry| {
+        entries.push(entry);
+        true
+    })?;
+
+    match format.as_str() {
+        "jsonl" => {
+            let mut out = String::new();
+            for entry in &entries {
+                out.push_str(&serde_json::to_string(entry).map_err(|e| e.to_string())?);
+                out.push('\n');
+            }
+            Ok(out)
+        }
+        "csv" => {
+            let mut out = String::from("ts,tool,status,duration_ms,working_dir,session_id,approval,action,hash\n");
+            for entry in &entries {
+                let row = [
+                    entry.ts.to_string(),
+                    entry.tool.clone(),
+                    entry.status.clone(),
+                    entry.duration_ms.to_string(),
+                    entry.working_dir.clone().unwrap_or_default(),
+                    entry.session_id.clone().unwrap_or_default(),
+                    entry.approval.clone().unwrap_or_default(),
+                    entry.action.clone(),
+                    entry.hash.clone(),
+                ];
+                out.push_str(&row.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(","));
+                out.push('\n');
+            }
+            Ok(out)
+        }
+        other => Err(format!("Unsupported export format '{}' (use jsonl or csv)", other)),
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct AuditIssue {
+    // File name of the segment the line belongs to
+    pub file: String,
+    pub line: usize,
+    // "unparsable", "hash_mismatch", "missing_entries", "reordered", "bad_signature",
+    // "unsigned", "segment_link"; warnings use "timestamp_order"
+    pub kind: String,
+    pub detail: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct AuditVerification {
+    pub path: String,
+    pub ok: bool,
+    pub segments: usize,
+    pub total_lines: usize,
+    pub valid_entries: usize,
+    pub first_broken_file: Option<String>,
+    pub first_broken_line: Option<usize>,
+    // Hash the oldest remaining segment chains from; empty when the log starts at the first entry
+    // ever written, non-empty when older segments were removed by retention
+    pub anchor_hash: String,
+    // Entries accepted without a signature because they were written before signing started;
+    // only counted when a public key is given and legacy entries are allowed
+    pub unsigned_legacy: usize,
+    pub issues: Vec<AuditIssue>,
+    // Oddities that do not break the chain, e.g. a clock that went backwards between entries
+    pub warnings: Vec<AuditIssue>,
+}
+
+// Chain state carried from one segment into the next.
+#[derive(Default)]
+struct ChainCursor {
+    prev_hash: Option<String>,
+    prev_ts: Option<u64>,
+    // Set by the first signed header or entry; every entry after it must be signed
+    signing: bool,
+    // Accept unsigned entries from before signing started instead of reporting them
+    allow_legacy: bool,
+    unsigned_legacy: usize,
+}
+
+// Checks a segment header's own hash and, with a key, its signature. A header that starts
+// the signing era must be signed, and once signing has started no header may drop it.
+fn verify_header(
+    header: &SegmentHeader,
+    cursor: &mut ChainCursor,
+    public_key: Option<&VerifyingKey>,
+) -> Option<(&'static str, String)> {
+    let signed_era = header.signed_from > 0;
+    if header.hash.is_empty() {
+        if signed_era {
+            return Some(("hash_mismatch", "segment header records signing but has no hash".into()));
+        }
+    } else {
+        let recomputed = compute_header_hash(header);
+        if recomputed != header.hash {
+            return Some((
+                "hash_mismatch",
+                format!("segment header hash {} does not match recomputed {}", header.hash, recomputed),
+            ));
+        }
+    }
+    let key = public_key?;
+    cursor.signing |= signed_era || !header.sig.is_empty();
+    if header.sig.is_empty() {
+        if cursor.signing {
+            return Some(("unsigned", "segment header has no signature but signing had started".into()));
+        }
+        return None;
+    }
+    if !signature_valid(key, &header.hash, &header.sig) {
+        return Some(("bad_signature", "segment header signature does not verify against the audit public key".into()));
+    }
+    None
+}
+
+// Returns (lines, valid entries) for one segment and pushes its issues.
+fn verify_segment(
+    file: &str,
+    content: &str,
+    cursor: &mut ChainCursor,
+    anchor_hash: &mut String,
+    public_key: Option<&VerifyingKey>,
+    issues: &mut Vec<AuditIssue>,
+    warnings: &mut Vec<AuditIssue>,
+) -> (usize, usize) {
+    let mut segment_issues = Vec::new();
+    let mut total_lines = 0;
+    let mut valid_entries = 0;
+    let issue = |line: usize, kind: &str, detail: String| AuditIssue {
+        file: file.to_string(),
+        line,
+        kind: kind.into(),
+        detail,
+    };
+
+    let mut entries: Vec<(usize, AuditEntry)> = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if entries.is_empty() && total_lines == 0 {
+            if let Ok(header) = serde_json::from_str::<SegmentHeader>(line) {
+                if let Some((kind, detail)) = verify_header(&header, cursor, public_key) {
+                    segment_issues.push(issue(i + 1, kind, detail));
+                }
+                match &cursor.prev_hash {
+                    Some(prev) if *prev != header.prev_hash => segment_issues.push(issue(
+                        i + 1,
+                        "segment_link",
+                        format!(
+                            "segment header expects the previous segment to end with {} but it ends with {}",
+                            header.prev_hash, prev
+                        ),
+                    )),
+                    Some(_) => {}
+                    None => *anchor_hash = header.prev_hash.clone(),
+                }
+                cursor.prev_hash = Some(header.prev_hash);
+                continue;
+            }
+        }
+        total_lines += 1;
+        match serde_json::from_str::<AuditEntry>(line) {
+            Ok(entry) => entries.push((i + 1, entry)),
+            Err(e) => segment_issues.push(issue(i + 1, "unparsable", e.to_string())),
+        }
+    }
+
+    let line_of_hash: std::collections::HashMap<&str, usize> =
+        entries.iter().map(|(line, e)| (e.hash.as_str(), *line)).collect();
+    for (line, entry) in &entries {
+        let line = *line;
+        let recomputed = compute_hash(entry);
+        let mut entry_ok = true;
+        if recomputed != entry.hash {
+            entry_ok = false;
+            segment_issues.push(issue(
+                line,
+                "hash_mismatch",
+                format!("stored hash {} does not match recomputed {}", entry.hash, recomputed),
+            ));
+        }
+
+        let expected_prev = cursor.prev_hash.as_deref().unwrap_or("");
+        if entry.prev_hash != expected_prev {
+            entry_ok = false;
+            match line_of_hash.get(entry.prev_hash.as_str()) {
+                Some(other) => segment_issues.push(issue(
+                    line,
+                    "reordered",
+                    format!("entry links to line {} instead of the entry before it", other),
+                )),
+                None => segment_issues.push(issue(
+                    line,
+                    "missing_entries",
+                    if cursor.prev_hash.is_none() {
+                        "first entry does not start the chain; earlier entries were removed".into()
+                    } else {
+                        format!("previous hash {} is not in the log; entries were removed", entry.prev_hash)
+                    },
+                )),
+            }
+        }
+
+        if let Some(prev_ts) = cursor.prev_ts {
+            if entry.ts < prev_ts {
+                warnings.push(issue(
+                    line,
+                    "timestamp_order",
+                    format!("timestamp {} is earlier than the previous entry ({})", entry.ts, prev_ts),
+                ));
+            }
+        }
+
+        if let Some(key) = public_key {
+            if entry.sig.is_empty() {
+                if entry.schema_version >= SIGNED_SCHEMA_VERSION || cursor.signing {
+                    entry_ok = false;
+                    segment_issues.push(issue(
+                        line,
+                        "unsigned",
+                        if cursor.signing {
+                            "entry has no signature but signing had already started".into()
+                        } else {
+                            format!("schema version {} entries must be signed", entry.schema_version)
+                        },
+                    ));
+                } else if cursor.allow_legacy {
+                    cursor.unsigned_legacy += 1;
+                } else {
+                    entry_ok = false;
+                    segment_issues.push(issue(
+                        line,
+                        "unsigned",
+                        "entry predates signing; legacy entries must be allowed explicitly".into(),
+                    ));
+                }
+            } else {
+                cursor.signing = true;
+                if !signature_valid(key, &entry.hash, &entry.sig) {
+                    entry_ok = false;
+                    segment_issues.push(issue(
+                        line,
+                        "bad_signature",
+                        "signature does not verify against the audit public key".into(),
+                    ));
+                }
+            }
+        }
+
+        if entry_ok {
+            valid_entries += 1;
+        }
+        cursor.prev_hash = Some(entry.hash.clone());
+        cursor.prev_ts = Some(entry.ts);
+    }
+
+    segment_issues.sort_by_key(|issue| issue.line);
+    issues.extend(segment_issues);
+    (total_lines, valid_entries)
+}
+
+// Recomputes every hash in the given files (oldest first) and checks each entry links to the
+// one before it, including across segment boundaries via the segment headers.
+// With a public key, signatures are checked too: every entry of SIGNED_SCHEMA_VERSION or later,
+// and everything after the first signed header or entry, must carry a valid signature. Older
+// unsigned entries are only accepted with `allow_legacy` and are counted in `unsigned_legacy`,
+// and a log with no signature at all never passes, since any log can be rewritten to look
+// like it predates signing.
+// Line numbers are 1-based so they match what an editor shows.
+fn verify_files(
+    display: String,
+    files: &[PathBuf],
+    public_key: Option<&VerifyingKey>,
+    allow_legacy: bool,
+) -> Result<AuditVerification, String> {
+    let mut issues = Vec::new();
+    let mut warnings = Vec::new();
+    let mut total_lines = 0;
+    let mut valid_entries = 0;
+    let mut anchor_hash = String::new();
+    let mut cursor = ChainCursor { allow_legacy, ..Default::default() };
+
+    for path in files {
+        let content = read_segment(path)?;
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let (lines, valid) = verify_segment(
+            &name,
+            &content,
+            &mut cursor,
+            &mut anchor_hash,
+            public_key,
+            &mut issues,
+            &mut warnings,
+        );
+        total_lines += lines;
+        valid_entries += valid;
+    }
+    if public_key.is_some() && !cursor.signing && total_lines > 0 {
+        let file = files.first().and_then(|path| path.file_name()).map(|n| n.to_string_lossy().to_string());
+        issues.insert(0, AuditIssue {
+            file: file.unwrap_or_default(),
+            line: 1,
+            kind: "unsigned".into(),
+            detail: "no segment header or entry is signed, so the log cannot be checked against the key".into(),
+        });
+    }
+
+    Ok(AuditVerification {
+        path: display,
+        ok: issues.is_empty(),
+        segments: files.len(),
+        total_lines,
+        valid_entries,
+        first_broken_file: issues.first().map(|issue| issue.file.clone()),
+        first_broken_line: issues.first().map(|issue| issue.line),
+        anchor_hash,
+        unsigned_legacy: cursor.unsigned_legacy,
+        issues,
+        warnings,
+    })
+}
+
+// A single file, plain or gzip-compressed.
+pub fn verify_audit_file(path: &Path, public_key: Option<&VerifyingKey>, allow_legacy: bool) -> Result<AuditVerification, String> {
+    let files = if path.exists() { vec![path.to_path_buf()] } else { Vec::new() };
+    verify_files(path.display().to_string(), &files, public_key, allow_legacy)
+}
+
+// The whole chain: every rotated segment next to `active`, then `active` itself.
+pub fn verify_audit_chain(active: &Path, public_key: Option<&VerifyingKey>, allow_legacy: bool) -> Result<AuditVerification, String> {
+    verify_files(active.display().to_string(), &chain_files(active), public_key, allow_legacy)
+}
+
+#[tauri::command]
+pub fn verify_audit_log(state: State<'_, AuditState>) -> Result<AuditVerification, String> {
+    let public_key = state.signing_key.lock().unwrap().as_ref().map(|k| k.verifying_key());
+    let path_guard = state.log_path.lock().unwrap();
+    match &*path_guard {
+        // The app's own log may start with entries written before an upgrade added signing;
+        // a log rewritten entirely as such entries still fails for carrying no signature
+        Some(path) => verify_audit_chain(path, public_key.as_ref(), true),
+        None => Err("Audit log path not initialized".to_string()),
+    }
+}
+
+// Hex-encoded Ed25519 public key for external verifiers (`verify-audit --public-key`).
+#[tauri::command]
+pub fn export_audit_public_key(state: State<'_, AuditState>) -> Result<String, String> {
+    match &*state.signing_key.lock().unwrap() {
+        Some(key) => Ok(to_hex(key.verifying_key().as_bytes())),
+        None => Err("Audit signing key is unavailable (OS keyring not accessible)".to_string()),
+    }
+}
+
+// `deskwork verify-audit <path> [--public-key <hex> [--allow-legacy]]`: prints the report and returns the process exit code.
+// Pointing at `audit.jsonl` (or the config dir) verifies the full chain including rotated segments;
+// any other file is verified on its own, anchored at its segment header.
+pub fn verify_audit_cli(args: &[String]) -> i32 {
+    let usage = "Usage: deskwork verify-audit <audit.jsonl | config dir | segment file> [--public-key <hex> [--allow-legacy]]";
+    let mut path = None;
+    let mut public_key = None;
+    let mut allow_legacy = false;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--allow-legacy" {
+            allow_legacy = true;
+        } else if arg == "--public-key" {
+            let Some(hex_key) = iter.next() else {
+                eprintln!("{}", usage);
+                return 2;
+            };
+            match parse_public_key(hex_key) {
+                Ok(key) => public_key = Some(key),
+                Err(e) => {
+                    eprintln!("Invalid public key: {}", e);
+                    return 2;
+                }
+            }
+        } else {
+            path = Some(arg.clone());
+        }
+    }
+    let Some(path) = path else {
+        eprintln!("{}", usage);
+        return 2;
+    };
+    let target = Path::new(&path);
+    let result = if target.is_dir() {
+        verify_audit_chain(&target.join(ACTIVE_LOG), public_key.as_ref(), allow_legacy)
+    } else if target.file_name().is_some_and(|name| name == ACTIVE_LOG) {
+        verify_audit_chain(target, public_key.as_ref(), allow_legacy)
     } else {
-        Ok(Vec::new())
+        verify_audit_file(target, public_key.as_ref(), allow_legacy)
+    };
+    match result {
+        Ok(report) => {
+            println!(
+                "{}: {} segment(s), {} line(s), {} entries verified",
+                report.path, report.segments, report.total_lines, report.valid_entries
+            );
+            if !report.anchor_hash.is_empty() {
+                println!("  chain starts after {} (older segments were pruned)", report.anchor_hash);
+            }
+            if report.unsigned_legacy > 0 {
+                println!("  {} entries predate signing and were accepted unsigned (--allow-legacy)", report.unsigned_legacy);
+            }
+            for issue in &report.issues {
+                println!("  {} line {}: {} - {}", issue.file, issue.line, issue.kind, issue.detail);
+            }
+            for warning in &report.warnings {
+                println!("  warning: {} line {}: {} - {}", warning.file, warning.line, warning.kind, warning.detail);
+            }
+            if report.ok {
+                if public_key.is_some() && report.unsigned_legacy > 0 {
+                    println!("OK: hash chain intact, signatures valid where present");
+                } else if public_key.is_some() {
+                    println!("OK: hash chain intact, signatures valid");
+                } else {
+                    println!("OK: hash chain intact (signatures not checked; pass --public-key)");
+                }
+                0
+            } else {
+                println!(
+                    "FAILED: first broken link at {} line {}",
+                    report.first_broken_file.unwrap_or_default(),
+                    report.first_broken_line.unwrap_or(0)
+                );
+                1
+            }
+        }
+        Err(e) => {
+            eprintln!("Could not read {}: {}", path, e);
+            2
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_key() -> SigningKey {
+        SigningKey::from_bytes(&[7u8; 32])
+    }
+
+    fn sign(hash: &str, key: &SigningKey) -> String {
+        to_hex(&key.sign(hash.as_bytes()).to_bytes())
+    }
+
+    fn chain(prev_hash: &str, first_ts: u64, count: usize, version: u32, key: Option<&SigningKey>) -> Vec<AuditEntry> {
+        let mut prev = prev_hash.to_string();
+        (0..count)
+            .map(|i| {
+                let mut entry = AuditEntry {
+                    schema_version: version,
+                    ts: first_ts + i as u64,
+                    tool: "write_file".into(),
+                    status: "success".into(),
+                    action: format!("wrote notes-{}.txt", i),
+                    duration_ms: 3,
+                    working_dir: Some("/work".into()),
+                    prev_hash: prev.clone(),
+                    hash: String::new(),
+                    session_id: None,
+                    approval: None,
+                    sig: String::new(),
+                };
+                entry.hash = compute_hash(&entry);
+                if let Some(key) = key {
+                    entry.sig = sign(&entry.hash, key);
+                }
+                prev = entry.hash.clone();
+                entry
+            })
+            .collect()
+    }
+
+    fn render(header: &SegmentHeader, entries: &[AuditEntry]) -> String {
+        std::iter::once(serde_json::to_string(header).unwrap())
+            .chain(entries.iter().map(|e| serde_json::to_string(e).unwrap()))
+            .map(|line| line + "\n")
+            .collect()
+    }
+
+    fn verify(segments: &[String], key: &SigningKey) -> Vec<AuditIssue> {
+        let public_key = key.verifying_key();
+        let mut cursor = ChainCursor::default();
+        let mut anchor_hash = String::new();
+        let mut issues = Vec::new();
+        let mut warnings = Vec::new();
+        for content in segments {
+            verify_segment(
+                "audit.jsonl",
+                content,
+                &mut cursor,
+                &mut anchor_hash,
+                Some(&public_key),
+                &mut issues,
+                &mut warnings,
+            );
+        }
+        issues
+    }
+
+    fn verify_with_warnings(content: &str) -> (Vec<AuditIssue>, Vec<AuditIssue>) {
+        let mut issues = Vec::new();
+        let mut warnings = Vec::new();
+        verify_segment(
+            "audit.jsonl",
+            content,
+            &mut ChainCursor::default(),
+            &mut String::new(),
+            None,
+            &mut issues,
+            &mut warnings,
+        );
+        (issues, warnings)
+    }
+
+    fn kinds(issues: &[AuditIssue]) -> Vec<&str> {
+        issues.iter().map(|issue| issue.kind.as_str()).collect()
+    }
+
+    #[test]
+    fn signed_chain_verifies() {
+        let key = test_key();
+        let header = SegmentHeader::new(100, String::new(), Some(&key));
+        let entries = chain("", 100, 3, SCHEMA_VERSION, Some(&key));
+        assert!(verify(&[render(&header, &entries)], &key).is_empty());
+    }
+
+    #[test]
+    fn stripped_signatures_are_rejected() {
+        let key = test_key();
+        let header = SegmentHeader::new(100, String::new(), Some(&key));
+        let mut entries = chain("", 100, 3, SCHEMA_VERSION, Some(&key));
+        for entry in &mut entries {
+            entry.sig.clear();
+        }
+        assert_eq!(kinds(&verify(&[render(&header, &entries)], &key)), ["unsigned"; 3]);
+
+        // Downgrading the entries to a pre-signing version does not help under a signed header
+        let downgraded = chain("", 100, 3, 1, None);
+        assert_eq!(kinds(&verify(&[render(&header, &downgraded)], &key)), ["unsigned"; 3]);
+
+        // Nor does stripping the header's signature, since it records that signing had started
+        let mut unsigned_header = header.clone();
+        unsigned_header.sig.clear();
+        assert_eq!(kinds(&verify(&[render(&unsigned_header, &downgraded)], &key)), ["unsigned"; 4]);
+    }
+
+    #[test]
+    fn rewritten_segment_after_a_signed_one_is_rejected() {
+        let key = test_key();
+        let first = chain("", 100, 2, SCHEMA_VERSION, Some(&key));
+        let first_segment = render(&SegmentHeader::new(100, String::new(), Some(&key)), &first);
+        let tail = first.last().unwrap().hash.clone();
+
+        // Rewritten as if it predated signing: legacy header, version 1 entries, fresh hashes
+        let forged_header = SegmentHeader::new(200, tail.clone(), None);
+        let forged = chain(&tail, 200, 2, 1, None);
+        let issues = verify(&[first_segment, render(&forged_header, &forged)], &key);
+        assert_eq!(kinds(&issues), ["unsigned"; 3]);
+        assert_eq!(issues[0].line, 1);
+    }
+
+    #[test]
+    fn fully_downgraded_log_is_rejected() {
+        let key = test_key();
+        let public_key = key.verifying_key();
+        let path = std::env::temp_dir().join(format!("deskwork-audit-{}.jsonl", uuid::Uuid::new_v4()));
+        let verify_file = |allow_legacy| verify_audit_file(&path, Some(&public_key), allow_legacy).unwrap();
+
+        // Every header and entry rewritten as if it predated signing
+        let legacy = chain("", 100, 3, 1, None);
+        fs::write(&path, render(&SegmentHeader::new(100, String::new(), None), &legacy)).unwrap();
+        assert!(!verify_file(false).ok);
+        let report = verify_file(true);
+        assert!(!report.ok);
+        assert_eq!(report.issues[0].kind, "unsigned");
+
+        // A genuine upgrade: legacy entries, then signing takes over
+        let tail = legacy.last().unwrap().hash.clone();
+        let mut upgraded = legacy.clone();
+        upgraded.extend(chain(&tail, 200, 2, SCHEMA_VERSION, Some(&key)));
+        fs::write(&path, render(&SegmentHeader::new(100, String::new(), None), &upgraded)).unwrap();
+        assert_eq!(kinds(&verify_file(false).issues), ["unsigned"; 3]);
+        let report = verify_file(true);
+        assert!(report.ok);
+        assert_eq!(report.unsigned_legacy, 3);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn reordered_entries_are_detected() {
+        let key = test_key();
+        let header = SegmentHeader::new(100, String::new(), Some(&key));
+        let mut entries = chain("", 100, 4, SCHEMA_VERSION, Some(&key));
+        entries.swap(1, 2);
+        let issues = verify(&[render(&header, &entries)], &key);
+        assert!(kinds(&issues).contains(&"reordered"));
+        assert!(issues.iter().all(|issue| issue.kind != "hash_mismatch"));
+    }
+
+    #[test]
+    fn clock_going_backwards_is_only_a_warning() {
+        let mut entries = chain("", 100, 3, SCHEMA_VERSION, None);
+        entries[2].ts = 50;
+        entries[2].hash = compute_hash(&entries[2]);
+        let (issues, warnings) = verify_with_warnings(&render(&SegmentHeader::new(100, String::new(), None), &entries));
+        assert!(issues.is_empty());
+        assert_eq!(kinds(&warnings), ["timestamp_order"]);
+    }
+
+    #[test]
+    fn unknown_cursor_is_an_error() {
+        let dir = std::env::temp_dir().join(format!("deskwork-audit-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let active = dir.join(ACTIVE_LOG);
+        let entries = chain("", 100, 2, SCHEMA_VERSION, None);
+        fs::write(&active, render(&SegmentHeader::new(100, String::new(), None), &entries)).unwrap();
+
+        let page = |cursor: &str| {
+            let query = AuditQuery { cursor: Some(cursor.to_string()), ..Default::default() };
+            let mut seen = Vec::new();
+            scan_newest_first(&active, &query, |entry| {
+                seen.push(entry.hash);
+                true
+            })
+            .map(|_| seen)
+        };
+        assert_eq!(page(&entries[1].hash).unwrap(), [entries[0].hash.clone()]);
+        assert!(page("0123").is_err());
+        let _ = fs::remove_dir_all(&dir);
     }
 }
@@ -13,34 +13,59 @@ mod audit;
 mod templates;
 mod skills;
 mod logging;
+mod policy;
+mod redact;
+mod vault;
+mod transcript;
+mod schema;
 
 use agent::AgentState;
-use settings::{SettingsState, load_initial_settings};
 use session_manager::SessionState;
-use agent::ApprovalState;
 use templates::TemplateState;
 use skills::SkillState;
 
 use tauri::Manager;
 
+pub use audit::verify_audit_cli;
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let initial_settings = load_initial_settings();
-
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .manage(AgentState::default())
-        .manage(ApprovalState::default())
-        .manage(SettingsState(std::sync::Mutex::new(initial_settings)))
         .manage(SessionState::default())
         .manage(SkillState::default())
         .setup(|app| {
+            // Logging comes up with defaults first so problems loading settings are recorded
+            logging::init_log_dir(app.handle());
+            logging::init_tracing();
+            schema::init(app.handle());
+            let settings_state = settings::init(app.handle());
+            let initial_settings = settings_state.0.lock().unwrap().clone();
+            app.manage(settings_state);
+            logging::apply_settings(&initial_settings);
+            logging::init_exporter(&initial_settings);
+            logging::prune_logs(initial_settings.log_retention_days);
+            session_manager::init(app.handle());
+
             let template_state = TemplateState::new(app.handle());
             app.manage(template_state);
             
             let audit_state = audit::init(app.handle());
             app.manage(audit_state);
+
+            let approval_state = agent::init_approvals(app.handle());
+            app.manage(approval_state);
+
+            let policy_state = policy::init(app.handle());
+            app.manage(policy_state);
+
+            if let Err(e) = session_manager::apply_retention(app.handle(), initial_settings.session_retention_days) {
+                tracing::warn!("sessions: retention failed: {}", e);
+            }
+
+            agent::spawn_approval_reaper(app.handle().clone());
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -67,24 +92,54 @@ pub fn run() {
             agent::chat,
             agent::set_agent_mode,
             agent::get_agent_mode,
+            agent::list_grants,
+            agent::add_grant,
+            agent::revoke_grant,
+            agent::list_pending_approvals,
+            agent::resolve_approval,
             settings::save_settings,
             settings::get_settings,
             session_manager::list_sessions,
             session_manager::create_session,
             session_manager::switch_session,
+            session_manager::fork_session,
+            session_manager::branch_for_edit,
             session_manager::rename_session,
             session_manager::toggle_pin,
+            session_manager::set_session_model,
+            session_manager::delete_session,
+            session_manager::delete_sessions,
+            session_manager::archive_session,
+            session_manager::archive_sessions,
+            session_manager::apply_session_retention,
+            session_manager::set_session_privacy,
             session_manager::search_sessions,
             session_manager::export_sessions,
             session_manager::import_sessions,
+            transcript::export_session,
             audit::get_audit_log,
+            audit::query_audit_log,
+            audit::export_audit_log,
+            audit::verify_audit_log,
+            audit::export_audit_public_key,
             templates::list_templates,
             templates::save_template,
             templates::delete_template,
+            schema::get_storage_warnings,
             skills::list_skills,
             skills::toggle_skill,
-            logging::get_session_log
+            logging::get_session_log,
+            logging::get_session_log_events,
+            logging::clear_logs,
+            policy::explain_policy,
+            policy::set_session_policy,
+            policy::get_session_policy
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|_app, event| {
+            if let tauri::RunEvent::Exit = event {
+                logging::shutdown_tracing();
+            }
+        });
 }
@@ -1,42 +1,511 @@
 use std::fs::{self, OpenOptions};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+use std::sync::OnceLock;
 use chrono::Local;
-use tauri::AppHandle;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::layer::Layered;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Layer, Registry};
+use crate::settings::AppSettings;
+
+// Events with this target are written to the per-session log file. They pick up
+// `session_id`, `turn_id`, `tool` and `args_hash` from the enclosing spans
+// (`chat_turn`, `provider_call`, `tool_dispatch`); `kind` becomes the log level column.
+// The OTLP exporter sees these events too, so callers redact text before emitting it.
+pub const SESSION_TARGET: &str = "deskwork::session";
+
+// Rotated files are `<session>.log.1` (newest) .. `<session>.log.N` (oldest)
+const MAX_ROTATED_FILES: usize = 5;
+
+static STRUCTURED: AtomicBool = AtomicBool::new(false);
+static MAX_FILE_BYTES: AtomicU64 = AtomicU64::new(5 * 1024 * 1024);
+static LOG_DIR: OnceLock<PathBuf> = OnceLock::new();
+static TRACER_PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+// The OTLP layer is filled in once settings are loaded, after tracing is already up
+type BaseSubscriber = Layered<SessionFileLayer, Layered<EnvFilter, Registry>>;
+type OtelLayer = tracing_opentelemetry::OpenTelemetryLayer<BaseSubscriber, opentelemetry_sdk::trace::Tracer>;
+static OTEL_LAYER: OnceLock<reload::Handle<Option<OtelLayer>, BaseSubscriber>> = OnceLock::new();
+
+// Resolves the platform app-log dir (e.g. %LOCALAPPDATA%\<bundle id>\logs); called from setup.
+pub fn init_log_dir(app_handle: &AppHandle) {
+    if let Ok(dir) = app_handle.path().app_log_dir() {
+        let _ = LOG_DIR.set(dir);
+    }
+}
 
 pub fn get_log_dir() -> PathBuf {
-    // Use the current working directory (project root in dev)
-    // or fallback to a sensible location if CWD fails.
-    // Ideally, this creates .deskwork/logs in the folder where the user runs the app.
-    let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
-    let log_dir = cwd.join(".deskwork").join("logs");
+    // Before setup (or if the platform dir is unavailable) fall back to the temp dir,
+    // never the launch folder, so logs do not end up inside a user's repo.
+    let log_dir = LOG_DIR
+        .get()
+        .cloned()
+        .unwrap_or_else(|| std::env::temp_dir().join("deskwork").join("logs"));
     if !log_dir.exists() {
         let _ = fs::create_dir_all(&log_dir);
     }
     log_dir
 }
 
-pub fn log(_app_handle: &AppHandle, session_id: &str, level: &str, message: &str) {
+// One session log record. With `structured_logs` on it is written as a JSON line; otherwise
+// only `ts`, `level` and `message` are kept, as a `[ts] [LEVEL] message` text line.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct LogEvent {
+    #[serde(default)]
+    pub ts: String,
+    // USER, ASSISTANT, API, TOOL_START, TOOL_END
+    pub level: String,
+    #[serde(default)]
+    pub session_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub turn_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub args_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u128>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completion_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_tokens: Option<u64>,
+    #[serde(default)]
+    pub message: String,
+}
+
+impl LogEvent {
+    pub fn new(session_id: &str, level: &str, message: &str) -> Self {
+        Self {
+            session_id: session_id.to_string(),
+            level: level.to_string(),
+            message: message.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+// Short stable fingerprint of tool arguments, taken after redaction so it cannot be
+// brute-forced back into a secret.
+pub fn args_hash(args: &Value) -> String {
+    let digest = Sha256::digest(crate::redact::redact_value(args).to_string().as_bytes());
+    format!("{:x}", digest)[..16].to_string()
+}
+
+// Picks up the logging-related settings; called at startup and whenever settings are saved.
+pub fn apply_settings(settings: &AppSettings) {
+    STRUCTURED.store(settings.structured_logs, Ordering::Relaxed);
+    MAX_FILE_BYTES.store(settings.log_max_file_mb.saturating_mul(1024 * 1024), Ordering::Relaxed);
+}
+
+// Session ids come from the frontend; keep them from escaping the log dir.
+fn session_log_path(dir: &Path, session_id: &str) -> Result<PathBuf, String> {
+    if session_id.is_empty() || !session_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("Invalid session id '{}'", session_id));
+    }
+    Ok(dir.join(format!("{}.log", session_id)))
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    PathBuf::from(format!("{}.{}", path.display(), index))
+}
+
+// Shifts `<id>.log` -> `<id>.log.1` -> ... and drops the oldest beyond MAX_ROTATED_FILES.
+fn rotate_if_needed(path: &Path, incoming: usize) {
+    let max_bytes = MAX_FILE_BYTES.load(Ordering::Relaxed);
+    let size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    if max_bytes == 0 || size == 0 || size + incoming as u64 <= max_bytes {
+        return;
+    }
+    let _ = fs::remove_file(rotated_path(path, MAX_ROTATED_FILES));
+    for index in (1..MAX_ROTATED_FILES).rev() {
+        let from = rotated_path(path, index);
+        if from.exists() {
+            let _ = fs::rename(&from, rotated_path(path, index + 1));
+        }
+    }
+    let _ = fs::rename(path, rotated_path(path, 1));
+}
+
+fn write_event(mut event: LogEvent) {
     let dir = get_log_dir();
-    let path = dir.join(format!("{}.log", session_id));
-    
+    let Ok(path) = session_log_path(&dir, &event.session_id) else {
+        return;
+    };
+
     let now = Local::now();
-    let timestamp = now.format("%Y-%m-%d %H:%M:%S%.3f");
-    
-    let line = format!("[{}] [{}] {}\n", timestamp, level, message);
-    
+    event.message = crate::redact::redact(&event.message);
+
+    let line = if STRUCTURED.load(Ordering::Relaxed) {
+        event.ts = now.to_rfc3339_opts(chrono::SecondsFormat::Millis, false);
+        match serde_json::to_string(&event) {
+            Ok(json) => format!("{}\n", json),
+            Err(_) => return,
+        }
+    } else {
+        let timestamp = now.format("%Y-%m-%d %H:%M:%S%.3f");
+        format!("[{}] [{}] {}\n", timestamp, event.level, event.message)
+    };
+
+    rotate_if_needed(&path, line.len());
     if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
         let _ = file.write_all(line.as_bytes());
     }
 }
 
+// Span fields the session file layer needs, stored on each span when it opens.
+#[derive(Default)]
+struct SessionFields {
+    session_id: Option<String>,
+    turn_id: Option<String>,
+    tool: Option<String>,
+    args_hash: Option<String>,
+}
+
+impl Visit for SessionFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        let slot = match field.name() {
+            "session_id" => &mut self.session_id,
+            "turn_id" => &mut self.turn_id,
+            "tool" => &mut self.tool,
+            "args_hash" => &mut self.args_hash,
+            _ => return,
+        };
+        *slot = Some(value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.record_str(field, &format!("{:?}", value));
+    }
+}
+
+struct EventFields(LogEvent);
+
+impl Visit for EventFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        let event = &mut self.0;
+        match field.name() {
+            "kind" => event.level = value.to_string(),
+            "message" => event.message = value.to_string(),
+            "status" => event.status = Some(value.to_string()),
+            "session_id" => event.session_id = value.to_string(),
+            "turn_id" => event.turn_id = Some(value.to_string()),
+            "tool" => event.tool = Some(value.to_string()),
+            "args_hash" => event.args_hash = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        let event = &mut self.0;
+        match field.name() {
+            "duration_ms" => event.duration_ms = Some(value as u128),
+            "prompt_tokens" => event.prompt_tokens = Some(value),
+            "completion_tokens" => event.completion_tokens = Some(value),
+            "total_tokens" => event.total_tokens = Some(value),
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.record_str(field, &format!("{:?}", value));
+    }
+}
+
+// Writes SESSION_TARGET events to `<log dir>/<session_id>.log`, as text or JSONL.
+struct SessionFileLayer;
+
+impl<S> Layer<S> for SessionFileLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = SessionFields::default();
+        attrs.record(&mut fields);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(fields);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(fields) = span.extensions_mut().get_mut::<SessionFields>() {
+                values.record(fields);
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if event.metadata().target() != SESSION_TARGET {
+            return;
+        }
+        let mut visitor = EventFields(LogEvent::default());
+        event.record(&mut visitor);
+        let mut log = visitor.0;
+
+        // Innermost span wins
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope {
+                let extensions = span.extensions();
+                let Some(fields) = extensions.get::<SessionFields>() else {
+                    continue;
+                };
+                if log.session_id.is_empty() {
+                    log.session_id = fields.session_id.clone().unwrap_or_default();
+                }
+                log.turn_id = log.turn_id.take().or_else(|| fields.turn_id.clone());
+                log.tool = log.tool.take().or_else(|| fields.tool.clone());
+                log.args_hash = log.args_hash.take().or_else(|| fields.args_hash.clone());
+            }
+        }
+        if log.session_id.is_empty() {
+            return;
+        }
+        write_event(log);
+    }
+}
+
+// `otlp_endpoint` is the collector's OTLP/HTTP base URL, e.g. http://localhost:4318.
+// Ok(None) when no endpoint is set.
+fn otlp_tracer(endpoint: &str) -> Result<Option<opentelemetry_sdk::trace::Tracer>, String> {
+    let endpoint = endpoint.trim().trim_end_matches('/');
+    if endpoint.is_empty() {
+        return Ok(None);
+    }
+    let url = if endpoint.ends_with("/v1/traces") {
+        endpoint.to_string()
+    } else {
+        format!("{}/v1/traces", endpoint)
+    };
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(url)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name("deskwork").build())
+        .build();
+    let tracer = provider.tracer("deskwork");
+    let _ = TRACER_PROVIDER.set(provider);
+    Ok(Some(tracer))
+}
+
+// Called first in setup, before settings are loaded, so warnings raised while loading them
+// are logged too; `init_exporter` adds the OTLP exporter afterwards.
+// `DESKWORK_LOG` overrides the filter (default "info"). Session logs keep their own
+// `deskwork::session=info` directive so e.g. `DESKWORK_LOG=warn` does not switch them off;
+// naming the target in the variable overrides it.
+pub fn init_tracing() {
+    let spec = std::env::var("DESKWORK_LOG").unwrap_or_default();
+    let mut filter = EnvFilter::try_new(&spec)
+        .ok()
+        .filter(|_| !spec.trim().is_empty())
+        .unwrap_or_else(|| EnvFilter::new("info"));
+    if !spec.contains(SESSION_TARGET) {
+        filter = filter.add_directive(format!("{}=info", SESSION_TARGET).parse().expect("valid session directive"));
+    }
+    let (otel_layer, handle) = reload::Layer::new(None);
+    if tracing_subscriber::registry()
+        .with(filter)
+        .with(SessionFileLayer)
+        .with(otel_layer)
+        .try_init()
+        .is_ok()
+    {
+        let _ = OTEL_LAYER.set(handle);
+    }
+}
+
+// Called once settings are loaded; changing `otlp_endpoint` takes effect on the next launch.
+pub fn init_exporter(settings: &AppSettings) {
+    let tracer = match otlp_tracer(&settings.otlp_endpoint) {
+        Ok(Some(tracer)) => tracer,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::warn!("OTLP exporter disabled: {}", e);
+            return;
+        }
+    };
+    if let Some(handle) = OTEL_LAYER.get() {
+        if let Err(e) = handle.reload(Some(tracing_opentelemetry::layer().with_tracer(tracer))) {
+            tracing::warn!("OTLP exporter disabled: {}", e);
+        }
+    }
+}
+
+// Flushes spans still queued for the collector.
+pub fn shutdown_tracing() {
+    if let Some(provider) = TRACER_PROVIDER.get() {
+        let _ = provider.shutdown();
+    }
+}
+
+// Text lines (written while structured logs were off) become events with only ts/level/message.
+// Continuation lines of a multi-line message are folded into the previous event.
+fn parse_log(session_id: &str, content: &str) -> Vec<LogEvent> {
+    let mut events: Vec<LogEvent> = Vec::new();
+    for line in content.lines() {
+        if line.starts_with('{') {
+            if let Ok(event) = serde_json::from_str::<LogEvent>(line) {
+                events.push(event);
+                continue;
+            }
+        }
+        let parsed = line
+            .strip_prefix('[')
+            .and_then(|rest| rest.split_once("] ["))
+            .and_then(|(ts, rest)| rest.split_once("] ").map(|(level, message)| (ts, level, message)));
+        match (parsed, events.last_mut()) {
+            (Some((ts, level, message)), _) => events.push(LogEvent {
+                ts: ts.to_string(),
+                ..LogEvent::new(session_id, level, message)
+            }),
+            (None, Some(last)) => {
+                last.message.push('\n');
+                last.message.push_str(line);
+            }
+            (None, None) => {}
+        }
+    }
+    events
+}
+
+// The session a log file belongs to, for `<id>.log` and its rotated `<id>.log.<n>` files.
+fn log_file_session(name: &str) -> Option<&str> {
+    let (stem, rest) = name.split_once(".log")?;
+    let rotated = rest.strip_prefix('.').is_some_and(|index| index.parse::<usize>().is_ok());
+    (rest.is_empty() || rotated).then_some(stem)
+}
+
+// Files in the log dir belonging to `session_id` (or all sessions), current file included.
+// Other components share the dir, so only files named after a session UUID ever match.
+fn log_files(dir: &Path, session_id: Option<&str>) -> Vec<PathBuf> {
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    read_dir
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                return false;
+            };
+            match (log_file_session(name), session_id) {
+                (Some(stem), _) if uuid::Uuid::parse_str(stem).is_err() => false,
+                (Some(stem), Some(sid)) => stem == sid,
+                (Some(_), None) => true,
+                (None, _) => false,
+            }
+        })
+        .collect()
+}
+
+// Deletes log files not modified in `retention_days`; 0 keeps everything.
+pub fn prune_logs(retention_days: u64) -> usize {
+    if retention_days == 0 {
+        return 0;
+    }
+    let Some(cutoff) = SystemTime::now().checked_sub(Duration::from_secs(retention_days * 86_400)) else {
+        return 0;
+    };
+    log_files(&get_log_dir(), None)
+        .into_iter()
+        .filter(|path| {
+            fs::metadata(path)
+                .and_then(|m| m.modified())
+                .is_ok_and(|modified| modified < cutoff)
+        })
+        .filter(|path| fs::remove_file(path).is_ok())
+        .count()
+}
+
+// Contents of a session's log files, oldest rotated file first so events stay in order.
+fn read_session_log(session_id: &str) -> Result<Vec<String>, String> {
+    let path = session_log_path(&get_log_dir(), session_id)?;
+    let mut contents = Vec::new();
+    for file in (1..=MAX_ROTATED_FILES).rev().map(|index| rotated_path(&path, index)).chain([path.clone()]) {
+        if file.exists() {
+            contents.push(fs::read_to_string(file).map_err(|e| e.to_string())?);
+        }
+    }
+    Ok(contents)
+}
+
+// The raw log text, text and JSONL lines as written.
 #[tauri::command]
 pub fn get_session_log(_app: AppHandle, session_id: String) -> Result<String, String> {
+    let contents = read_session_log(&session_id)?;
+    if contents.is_empty() {
+        return Ok("No logs found for this session.".to_string());
+    }
+    Ok(contents.concat())
+}
+
+// The same log parsed into events, whichever format each line was written in.
+#[tauri::command]
+pub fn get_session_log_events(session_id: String) -> Result<Vec<LogEvent>, String> {
+    Ok(read_session_log(&session_id)?
+        .iter()
+        .flat_map(|content| parse_log(&session_id, content))
+        .collect())
+}
+
+// Removes the logs of one session, or every session log when `session_id` is omitted.
+// Returns the number of files deleted.
+#[tauri::command]
+pub fn clear_logs(session_id: Option<String>) -> Result<usize, String> {
     let dir = get_log_dir();
-    let path = dir.join(format!("{}.log", session_id));
-    if path.exists() {
-        fs::read_to_string(path).map_err(|e| e.to_string())
-    } else {
-        Ok("No logs found for this session.".to_string())
+    if let Some(sid) = &session_id {
+        session_log_path(&dir, sid)?;
+    }
+    let mut removed = 0;
+    for path in log_files(&dir, session_id.as_deref()) {
+        fs::remove_file(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_session_logs_are_cleared() {
+        let dir = std::env::temp_dir().join(format!("deskwork-logs-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let sid = uuid::Uuid::new_v4().to_string();
+        let session_files = [format!("{}.log", sid), format!("{}.log.2", sid)];
+        for name in session_files.iter().map(String::as_str).chain(["updater.log", "webview.log.1", "notes.log.txt"]) {
+            fs::write(dir.join(name), "x").unwrap();
+        }
+
+        let mut names: Vec<String> = log_files(&dir, None)
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, session_files);
+        assert_eq!(log_files(&dir, Some(&sid)).len(), 2);
+        assert!(log_files(&dir, Some("updater")).is_empty());
+        let _ = fs::remove_dir_all(&dir);
     }
 }
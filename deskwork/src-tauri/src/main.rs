@@ -2,5 +2,23 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("verify-audit") {
+        attach_parent_console();
+        std::process::exit(deskwork_lib::verify_audit_cli(&args[2..]));
+    }
     deskwork_lib::run()
 }
+
+// Release builds start without a console on Windows; attach to the launching shell's console
+// so the CLI's output is visible there.
+#[cfg(windows)]
+fn attach_parent_console() {
+    use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+    unsafe {
+        let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_parent_console() {}
@@ -0,0 +1,618 @@
+// Declarative tool policy.
+//
+// Rules are read from three layers plus the built-in defaults, checked in this order:
+//   0. enforced  - PLAN mode and read-only; no layer can allow past them (a user deny still
+//                  beats the read-only ask)
+//   1. session   - rules set at runtime for one session (set_session_policy)
+//   2. workspace - <working_dir>/.deskwork/policy.toml
+//   3. global    - <app_config_dir>/policy.toml
+//   4. builtin   - the defaults below (workspace scope, command allowlist, sensitive tools)
+// Within a layer the first matching rule wins. If nothing matches, the call is allowed.
+// Patterns are matched case-insensitively; `path` arguments are resolved (`.`/`..`, symlinks)
+// before matching, and workspace-relative patterns only see paths that stay inside it.
+//
+// Example policy.toml:
+//
+//   [[rules]]
+//   tool = "execute_command"
+//   args = { command = "cargo|npm" }
+//   decision = "allow"
+//
+//   [[rules]]
+//   tool = "write_file"
+//   args = { path = "secrets/*" }
+//   decision = "deny"
+//   reason = "Secrets are managed by hand"
+//
+// Workspace `allow` rules are ignored unless the global file sets `trust_workspace = true`,
+// so a cloned repository cannot grant itself permissions.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{Manager, State};
+
+use crate::agent::{AgentMode, AgentState};
+use crate::settings::SettingsState;
+
+const POLICY_FILE: &str = "policy.toml";
+const SENSITIVE_TOOLS: &[&str] = &[
+    "execute_command",
+    "write_file",
+    "open_app",
+    "create_docx",
+    "create_slide_deck",
+    "search_web",
+];
+const PLAN_BLOCKED_TOOLS: &[&str] = &["write_file", "execute_command", "create_docx", "create_slide_deck"];
+const SAFE_COMMANDS: &str = "ls|dir|pwd|cat|type|echo";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    Allow,
+    Ask,
+    Deny,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyLayer {
+    Session,
+    Workspace,
+    Global,
+    Builtin,
+    Default,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PolicyRule {
+    // Tool name pattern, e.g. "write_file", "mouse_*" or "*"
+    pub tool: String,
+    // Argument name -> pattern. Patterns support `*`, `?`, `a|b` alternatives and a leading `!` to negate.
+    #[serde(default)]
+    pub args: HashMap<String, String>,
+    // "inside" / "outside": where the `path` argument sits relative to the working directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    // "plan" / "build"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_only: Option<bool>,
+    pub decision: Decision,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PolicyFile {
+    #[serde(default)]
+    pub trust_workspace: bool,
+    #[serde(default)]
+    pub rules: Vec<PolicyRule>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PolicyVerdict {
+    pub decision: Decision,
+    pub layer: PolicyLayer,
+    pub rule_index: Option<usize>,
+    pub rule: Option<PolicyRule>,
+    pub source: Option<String>,
+    pub reason: String,
+}
+
+impl PolicyVerdict {
+    // Read-only and out-of-workspace asks are safety checks that need a fresh approval each
+    // time; only ordinary asks can be satisfied by a standing grant.
+    pub fn grantable(&self) -> bool {
+        let safety_check = self.layer == PolicyLayer::Builtin
+            && self.rule.as_ref().is_some_and(|r| r.read_only.is_some() || r.scope.is_some());
+        self.decision == Decision::Ask && !safety_check
+    }
+}
+
+pub struct PolicyContext<'a> {
+    pub mode: &'a AgentMode,
+    pub read_only: bool,
+    pub working_dir: &'a Option<String>,
+    pub session_id: Option<&'a str>,
+}
+
+#[derive(Default)]
+pub struct PolicyState {
+    pub global_path: Option<PathBuf>,
+    pub session_rules: Mutex<HashMap<String, Vec<PolicyRule>>>,
+}
+
+pub fn init(app_handle: &tauri::AppHandle) -> PolicyState {
+    let config_dir = app_handle.path().app_config_dir().unwrap_or_default();
+    if !config_dir.exists() {
+        let _ = fs::create_dir_all(&config_dir);
+    }
+    PolicyState {
+        global_path: Some(config_dir.join(POLICY_FILE)),
+        session_rules: Mutex::new(HashMap::new()),
+    }
+}
+
+fn rule(tool: &str, decision: Decision, reason: &str) -> PolicyRule {
+    PolicyRule {
+        tool: tool.to_string(),
+        args: HashMap::new(),
+        scope: None,
+        mode: None,
+        read_only: None,
+        decision,
+        reason: Some(reason.to_string()),
+    }
+}
+
+// Checked before any user layer.
+fn enforced_rules() -> Vec<PolicyRule> {
+    let mut rules = Vec::new();
+    for tool in PLAN_BLOCKED_TOOLS {
+        let mut r = rule(tool, Decision::Deny, "Disabled in PLAN mode. Switch to BUILD mode to execute.");
+        r.mode = Some("plan".into());
+        rules.push(r);
+    }
+    for tool in SENSITIVE_TOOLS {
+        let mut r = rule(tool, Decision::Ask, "Read-only mode is enabled");
+        r.read_only = Some(true);
+        rules.push(r);
+    }
+    rules
+}
+
+fn builtin_rules() -> Vec<PolicyRule> {
+    let mut rules = Vec::new();
+    for tool in ["write_file", "read_file"] {
+        let mut r = rule(tool, Decision::Ask, "Path is outside the active workspace");
+        r.scope = Some("outside".into());
+        rules.push(r);
+    }
+    let mut r = rule("execute_command", Decision::Ask, "Command is not in the allowlist");
+    r.args.insert("command".into(), format!("!{}", SAFE_COMMANDS));
+    rules.push(r);
+    let mut r = rule("open_app", Decision::Ask, "External URL requires approval");
+    r.args.insert("path".into(), "http*".into());
+    rules.push(r);
+    let mut r = rule("open_app", Decision::Ask, "Application path is outside the active workspace");
+    r.scope = Some("outside".into());
+    rules.push(r);
+    for tool in SENSITIVE_TOOLS {
+        rules.push(rule(tool, Decision::Ask, "Sensitive action requires explicit approval"));
+    }
+    rules
+}
+
+fn load_policy_file(path: &Path) -> Result<PolicyFile, String> {
+    if !path.exists() {
+        return Ok(PolicyFile::default());
+    }
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn workspace_policy_path(working_dir: &Option<String>) -> Option<PathBuf> {
+    match working_dir {
+        Some(dir) if !dir.is_empty() => Some(Path::new(dir).join(".deskwork").join(POLICY_FILE)),
+        _ => None,
+    }
+}
+
+// Simple glob: `*` matches any run of characters (including `/`), `?` matches one. ASCII case
+// is ignored, as the command allowlist always has been.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let t: Vec<char> = text.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    while pi < p.len() && p[pi] == '*' {
+        pi += 1;
+    }
+    pi == p.len()
+}
+
+pub fn pattern_matches(pattern: &str, candidates: &[String]) -> bool {
+    let (negate, pattern) = match pattern.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, pattern),
+    };
+    let hit = pattern
+        .split('|')
+        .any(|alt| candidates.iter().any(|c| glob_match(alt.trim(), c)));
+    hit != negate
+}
+
+fn arg_candidates(name: &str, value: &Value, working_dir: &Option<String>) -> Vec<String> {
+    let raw = match value {
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items
+            .iter()
+            .map(|v| v.as_str().map(|s| s.to_string()).unwrap_or_else(|| v.to_string()))
+            .collect::<Vec<_>>()
+            .join(" "),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    };
+    // URLs (open_app) are matched as written; file paths only in resolved form, so
+    // `src/../../etc/passwd` cannot pass for `src/*`
+    if name != "path" || raw.is_empty() || raw.contains("://") {
+        return vec![raw];
+    }
+    let mut candidates = vec![slash_path(&resolve_path(working_dir, &raw))];
+    // Let path patterns be written relative to the workspace ("src/*")
+    if let Some(rel) = workspace_relative(working_dir, &raw) {
+        candidates.push(rel);
+    }
+    candidates
+}
+
+fn slash_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+// Drops `.` and applies `..` without touching the filesystem; `..` stops at the root.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other.as_os_str()),
+        }
+    }
+    out
+}
+
+// Absolute form of `target`, with relative paths taken from the working directory. The longest
+// existing ancestor is canonicalized so symlinks are followed; the remainder is resolved
+// lexically, since a file about to be written does not exist yet.
+pub fn resolve_path(working_dir: &Option<String>, target: &str) -> PathBuf {
+    let joined = match working_dir.as_deref().filter(|dir| !dir.is_empty()) {
+        Some(base) => Path::new(base).join(target),
+        None => PathBuf::from(target),
+    };
+    let lexical = normalize(&joined);
+    let mut existing = lexical.as_path();
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(mut resolved) = existing.canonicalize() {
+            resolved.extend(rest.iter().rev());
+            return resolved;
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name);
+                existing = parent;
+            }
+            _ => return lexical,
+        }
+    }
+}
+
+// `target` relative to the workspace, or None when it resolves outside of it.
+pub fn workspace_relative(working_dir: &Option<String>, target: &str) -> Option<String> {
+    let base = working_dir.as_deref().filter(|dir| !dir.is_empty())?;
+    let root = resolve_path(&None, base);
+    let resolved = resolve_path(working_dir, target);
+    resolved.strip_prefix(&root).ok().map(slash_path)
+}
+
+pub fn path_out_of_scope(working_dir: &Option<String>, target: &str) -> bool {
+    working_dir.as_deref().is_some_and(|dir| !dir.is_empty()) && workspace_relative(working_dir, target).is_none()
+}
+
+// Tool and argument patterns only; shared with standing approval grants.
+pub fn call_matches(
+    tool_pattern: &str,
+    arg_patterns: &HashMap<String, String>,
+    tool: &str,
+    args: &Value,
+    working_dir: &Option<String>,
+) -> bool {
+    if !pattern_matches(tool_pattern, &[tool.to_string()]) {
+        return false;
+    }
+    arg_patterns.iter().all(|(name, pattern)| {
+        let candidates = arg_candidates(name, &args[name.as_str()], working_dir);
+        if name == "path" {
+            pattern_matches(&pattern.replace('\\', "/"), &candidates)
+        } else {
+            pattern_matches(pattern, &candidates)
+        }
+    })
+}
+
+fn rule_matches(rule: &PolicyRule, tool: &str, args: &Value, ctx: &PolicyContext<'_>) -> bool {
+    if !call_matches(&rule.tool, &rule.args, tool, args, ctx.working_dir) {
+        return false;
+    }
+    if let Some(mode) = &rule.mode {
+        if !format!("{:?}", ctx.mode).eq_ignore_ascii_case(mode) {
+            return false;
+        }
+    }
+    if let Some(read_only) = rule.read_only {
+        if read_only != ctx.read_only {
+            return false;
+        }
+    }
+    if let Some(scope) = &rule.scope {
+        let path = args["path"].as_str().unwrap_or("");
+        let outside = path_out_of_scope(ctx.working_dir, path);
+        let wanted_outside = scope.eq_ignore_ascii_case("outside");
+        if outside != wanted_outside {
+            return false;
+        }
+    }
+    true
+}
+
+fn first_match(
+    rules: &[PolicyRule],
+    layer: PolicyLayer,
+    source: Option<&Path>,
+    tool: &str,
+    args: &Value,
+    ctx: &PolicyContext<'_>,
+) -> Option<PolicyVerdict> {
+    rules.iter().enumerate().find_map(|(i, rule)| {
+        if !rule_matches(rule, tool, args, ctx) {
+            return None;
+        }
+        Some(PolicyVerdict {
+            decision: rule.decision,
+            layer,
+            rule_index: Some(i),
+            rule: Some(rule.clone()),
+            source: source.map(|p| p.display().to_string()),
+            reason: rule
+                .reason
+                .clone()
+                .unwrap_or_else(|| format!("Matched {:?} rule #{}", layer, i + 1).to_lowercase()),
+        })
+    })
+}
+
+// A broken policy file should never silently widen permissions
+fn broken(layer: PolicyLayer, err: String) -> PolicyVerdict {
+    PolicyVerdict {
+        decision: Decision::Ask,
+        layer,
+        rule_index: None,
+        rule: None,
+        source: None,
+        reason: format!("Policy file could not be parsed ({})", err),
+    }
+}
+
+// Session, workspace and global rules, in that order.
+fn user_layers(state: &PolicyState, tool: &str, args: &Value, ctx: &PolicyContext<'_>) -> Option<PolicyVerdict> {
+    if let Some(sid) = ctx.session_id {
+        let session_rules = state.session_rules.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(rules) = session_rules.get(sid) {
+            if let Some(v) = first_match(rules, PolicyLayer::Session, None, tool, args, ctx) {
+                return Some(v);
+            }
+        }
+    }
+
+    let global = match &state.global_path {
+        Some(path) => match load_policy_file(path) {
+            Ok(file) => file,
+            Err(e) => return Some(broken(PolicyLayer::Global, e)),
+        },
+        None => PolicyFile::default(),
+    };
+
+    if let Some(path) = workspace_policy_path(ctx.working_dir) {
+        let workspace = match load_policy_file(&path) {
+            Ok(file) => file,
+            Err(e) => return Some(broken(PolicyLayer::Workspace, e)),
+        };
+        let rules: Vec<PolicyRule> = workspace
+            .rules
+            .into_iter()
+            .filter(|r| global.trust_workspace || r.decision != Decision::Allow)
+            .collect();
+        if let Some(v) = first_match(&rules, PolicyLayer::Workspace, Some(&path), tool, args, ctx) {
+            return Some(v);
+        }
+    }
+
+    first_match(&global.rules, PolicyLayer::Global, state.global_path.as_deref(), tool, args, ctx)
+}
+
+pub fn evaluate(state: &PolicyState, tool: &str, args: &Value, ctx: &PolicyContext<'_>) -> PolicyVerdict {
+    let enforced = first_match(&enforced_rules(), PolicyLayer::Builtin, None, tool, args, ctx);
+    if let Some(v) = enforced.as_ref().filter(|v| v.decision == Decision::Deny) {
+        return v.clone();
+    }
+    let layered = user_layers(state, tool, args, ctx);
+    if let Some(v) = layered.as_ref().filter(|v| v.decision == Decision::Deny) {
+        return v.clone();
+    }
+    if let Some(v) = enforced.or(layered) {
+        return v;
+    }
+
+    if let Some(v) = first_match(&builtin_rules(), PolicyLayer::Builtin, None, tool, args, ctx) {
+        return v;
+    }
+
+    PolicyVerdict {
+        decision: Decision::Allow,
+        layer: PolicyLayer::Default,
+        rule_index: None,
+        rule: None,
+        source: None,
+        reason: "No rule matched".to_string(),
+    }
+}
+
+#[tauri::command]
+pub fn explain_policy(
+    tool: String,
+    args: Value,
+    working_dir: Option<String>,
+    session_id: Option<String>,
+    state: State<'_, PolicyState>,
+    agent_state: State<'_, AgentState>,
+    settings_state: State<'_, SettingsState>,
+) -> Result<PolicyVerdict, String> {
+    let mode = agent_state.mode.lock().map_err(|e| e.to_string())?.clone();
+    let read_only = settings_state.0.lock().map_err(|e| e.to_string())?.read_only;
+    let ctx = PolicyContext {
+        mode: &mode,
+        read_only,
+        working_dir: &working_dir,
+        session_id: session_id.as_deref(),
+    };
+    Ok(evaluate(&state, &tool, &args, &ctx))
+}
+
+#[tauri::command]
+pub fn set_session_policy(
+    session_id: String,
+    rules: Vec<PolicyRule>,
+    state: State<'_, PolicyState>,
+) -> Result<(), String> {
+    let mut session_rules = state.session_rules.lock().map_err(|e| e.to_string())?;
+    if rules.is_empty() {
+        session_rules.remove(&session_id);
+    } else {
+        session_rules.insert(session_id, rules);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_session_policy(session_id: String, state: State<'_, PolicyState>) -> Result<Vec<PolicyRule>, String> {
+    let session_rules = state.session_rules.lock().map_err(|e| e.to_string())?;
+    Ok(session_rules.get(&session_id).cloned().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn ctx<'a>(mode: &'a AgentMode, read_only: bool, working_dir: &'a Option<String>) -> PolicyContext<'a> {
+        PolicyContext { mode, read_only, working_dir, session_id: Some("s1") }
+    }
+
+    fn state_with_session_rule(r: PolicyRule) -> PolicyState {
+        let state = PolicyState::default();
+        state.session_rules.lock().unwrap().insert("s1".into(), vec![r]);
+        state
+    }
+
+    fn allow_cargo() -> PolicyRule {
+        let mut r = rule("execute_command", Decision::Allow, "cargo is fine");
+        r.args.insert("command".into(), "cargo|npm".into());
+        r
+    }
+
+    fn workspace() -> (PathBuf, Option<String>) {
+        let dir = std::env::temp_dir().join(format!("deskwork-policy-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        let wd = Some(dir.to_string_lossy().to_string());
+        (dir, wd)
+    }
+
+    #[test]
+    fn plan_mode_deny_beats_user_allow() {
+        let state = state_with_session_rule(allow_cargo());
+        let v = evaluate(&state, "execute_command", &json!({"command": "cargo"}), &ctx(&AgentMode::Plan, false, &None));
+        assert_eq!(v.decision, Decision::Deny);
+        assert_eq!(v.layer, PolicyLayer::Builtin);
+
+        let v = evaluate(&state, "execute_command", &json!({"command": "cargo"}), &ctx(&AgentMode::Build, false, &None));
+        assert_eq!(v.decision, Decision::Allow);
+        assert_eq!(v.layer, PolicyLayer::Session);
+    }
+
+    #[test]
+    fn read_only_ask_beats_user_allow_but_not_user_deny() {
+        let state = state_with_session_rule(allow_cargo());
+        let v = evaluate(&state, "execute_command", &json!({"command": "cargo"}), &ctx(&AgentMode::Build, true, &None));
+        assert_eq!(v.decision, Decision::Ask);
+        assert_eq!(v.reason, "Read-only mode is enabled");
+
+        let state = state_with_session_rule(rule("execute_command", Decision::Deny, "no"));
+        let v = evaluate(&state, "execute_command", &json!({"command": "cargo"}), &ctx(&AgentMode::Build, true, &None));
+        assert_eq!(v.decision, Decision::Deny);
+        assert_eq!(v.layer, PolicyLayer::Session);
+    }
+
+    #[test]
+    fn builtin_matching_ignores_case() {
+        let state = PolicyState::default();
+        let build = AgentMode::Build;
+        let v = evaluate(&state, "execute_command", &json!({"command": "LS"}), &ctx(&build, false, &None));
+        assert_eq!(v.reason, "Sensitive action requires explicit approval");
+        let v = evaluate(&state, "execute_command", &json!({"command": "Rm"}), &ctx(&build, false, &None));
+        assert_eq!(v.reason, "Command is not in the allowlist");
+        let v = evaluate(&state, "open_app", &json!({"path": "HTTPS://example.com"}), &ctx(&build, false, &None));
+        assert_eq!(v.reason, "External URL requires approval");
+    }
+
+    #[test]
+    fn relative_patterns_do_not_match_traversal() {
+        let (dir, wd) = workspace();
+        let patterns = HashMap::from([("path".to_string(), "src/*".to_string())]);
+        let matches = |path: &str| call_matches("write_file", &patterns, "write_file", &json!({"path": path}), &wd);
+
+        assert!(matches("src/main.rs"));
+        assert!(matches("src/new/file.rs"));
+        assert!(matches(&dir.join("src").join("lib.rs").to_string_lossy()));
+        assert!(!matches("src/../../etc/passwd"));
+        assert!(!matches(&format!("{}/src/../../etc/passwd", dir.display())));
+        assert!(!matches("src/../Cargo.toml"));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn scope_uses_resolved_paths() {
+        let (dir, wd) = workspace();
+        assert!(!path_out_of_scope(&wd, "src/not-yet-written.rs"));
+        assert!(!path_out_of_scope(&wd, "./src/../README.md"));
+        assert!(path_out_of_scope(&wd, "src/../../outside.txt"));
+        assert!(path_out_of_scope(&wd, "/etc/passwd"));
+        assert!(!path_out_of_scope(&None, "/etc/passwd"));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn out_of_scope_write_asks() {
+        let (dir, wd) = workspace();
+        let state = PolicyState::default();
+        let build = AgentMode::Build;
+        let v = evaluate(&state, "write_file", &json!({"path": "src/../../x"}), &ctx(&build, false, &wd));
+        assert_eq!(v.reason, "Path is outside the active workspace");
+        let _ = fs::remove_dir_all(dir);
+    }
+}
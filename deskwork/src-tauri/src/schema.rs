@@ -0,0 +1,240 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tauri::Manager;
+
+// Versioned records for what DeskWork persists as JSON (settings, templates, sessions, audit
+// entries). Every record carries `schema_version`; older records are upgraded on load by the
+// type's migrations, and files that cannot be read are moved to `<app_data_dir>/quarantine`
+// with a warning for the UI instead of being silently replaced by defaults.
+
+// `migrations[i]` upgrades a record from version i to i + 1, so a type's current version is
+// the length of its migration list. Version 0 is a record written before versioning existed.
+pub type Migration = fn(&mut Value) -> Result<(), String>;
+
+const QUARANTINE_DIR: &str = "quarantine";
+
+static QUARANTINE_PATH: OnceLock<PathBuf> = OnceLock::new();
+static WARNINGS: Mutex<Vec<StorageWarning>> = Mutex::new(Vec::new());
+// Unreadable files that could not be moved aside either; saves refuse to overwrite them
+static BLOCKED: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+#[derive(Serialize, Clone, Debug)]
+pub struct StorageWarning {
+    pub file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quarantined_to: Option<String>,
+    pub reason: String,
+    pub ts: u64,
+}
+
+// Records from before versioning already have the v1 shape; they only gain the field.
+pub fn unversioned(_: &mut Value) -> Result<(), String> {
+    Ok(())
+}
+
+pub const fn current_version(migrations: &[Migration]) -> u32 {
+    migrations.len() as u32
+}
+
+// Runs the migrations a record is missing and stamps it with the current version.
+pub fn upgrade(record: &mut Value, migrations: &[Migration]) -> Result<(), String> {
+    let current = current_version(migrations);
+    let Some(object) = record.as_object() else {
+        return Err("expected a JSON object".to_string());
+    };
+    let version = match object.get("schema_version") {
+        None => 0,
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or("schema_version is not a number")?,
+    };
+    if version > current {
+        return Err(format!(
+            "written by a newer DeskWork (schema version {}, this build reads up to {})",
+            version, current
+        ));
+    }
+    for (from, migrate) in migrations.iter().enumerate().skip(version as usize) {
+        migrate(record).map_err(|e| format!("migration from schema version {} failed: {}", from, e))?;
+    }
+    record["schema_version"] = Value::from(current);
+    Ok(())
+}
+
+pub fn parse<T: DeserializeOwned>(mut record: Value, migrations: &[Migration]) -> Result<T, String> {
+    upgrade(&mut record, migrations)?;
+    serde_json::from_value(record).map_err(|e| e.to_string())
+}
+
+pub fn init(app_handle: &tauri::AppHandle) {
+    if let Ok(data_dir) = app_handle.path().app_data_dir() {
+        let _ = QUARANTINE_PATH.set(data_dir.join(QUARANTINE_DIR));
+    }
+}
+
+fn record_warning(warning: StorageWarning) {
+    tracing::warn!(
+        "storage: {}: {}{}",
+        warning.file,
+        warning.reason,
+        warning.quarantined_to.as_deref().map(|p| format!(", moved to {}", p)).unwrap_or_default()
+    );
+    WARNINGS.lock().unwrap_or_else(|e| e.into_inner()).push(warning);
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+// A data file that needs the user's attention but was left where it is, e.g. a legacy file
+// that could not be migrated. Shown in the UI next to quarantined files.
+pub fn warn(path: &Path, reason: &str) {
+    record_warning(StorageWarning {
+        file: path.display().to_string(),
+        quarantined_to: None,
+        reason: reason.to_string(),
+        ts: now_secs(),
+    });
+}
+
+// Moves an unreadable file aside (as `<name>.<unix ts>`) so the next save cannot overwrite it.
+// A file that cannot be moved stays where it is and is blocked from being saved over instead.
+pub fn quarantine(path: &Path, reason: &str) -> Option<PathBuf> {
+    quarantine_into(QUARANTINE_PATH.get().map(PathBuf::as_path), path, reason)
+}
+
+fn quarantine_into(dir: Option<&Path>, path: &Path, reason: &str) -> Option<PathBuf> {
+    let ts = now_secs();
+    let target = dir.zip(path.file_name()).map(|(dir, name)| {
+        dir.join(format!("{}.{}", name.to_string_lossy(), ts))
+    });
+    let moved = target.filter(|to| crate::settings::move_legacy_file(path, to).is_ok());
+    let reason = if moved.is_none() {
+        BLOCKED.lock().unwrap_or_else(|e| e.into_inner()).push(path.to_path_buf());
+        format!("{}; it could not be moved aside and will not be saved over", reason)
+    } else {
+        reason.to_string()
+    };
+    record_warning(StorageWarning {
+        file: path.display().to_string(),
+        quarantined_to: moved.as_ref().map(|p| p.display().to_string()),
+        reason,
+        ts,
+    });
+    moved
+}
+
+// Called before writing a file `load_file` reads, so a file left in place by `quarantine`
+// is not replaced by the defaults the app fell back to.
+pub fn ensure_writable(path: &Path) -> Result<(), String> {
+    if BLOCKED.lock().unwrap_or_else(|e| e.into_inner()).iter().any(|blocked| blocked == path) {
+        return Err(format!(
+            "{} could not be read and was left in place; fix or remove it and restart DeskWork to save changes",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+// Reads and parses a JSON file. Missing files are None; unreadable ones are quarantined and
+// also come back as None, so callers fall back to their defaults without losing the data
+// (writers check `ensure_writable` for files that could not be moved).
+pub fn load_file<T>(path: &Path, parse: impl FnOnce(Value) -> Result<T, String>) -> Option<T> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            quarantine(path, &e.to_string());
+            return None;
+        }
+    };
+    let result = serde_json::from_str::<Value>(&content)
+        .map_err(|e| e.to_string())
+        .and_then(parse);
+    match result {
+        Ok(value) => Some(value),
+        Err(e) => {
+            quarantine(path, &e);
+            None
+        }
+    }
+}
+
+// Files quarantined or left unmigrated since startup, for the UI to surface.
+#[tauri::command]
+pub fn get_storage_warnings() -> Vec<StorageWarning> {
+    WARNINGS.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // v1 -> v2 renames `name` to `title`
+    fn rename_name(record: &mut Value) -> Result<(), String> {
+        let object = record.as_object_mut().ok_or("expected an object")?;
+        let name = object.remove("name").ok_or("missing name")?;
+        object.insert("title".into(), name);
+        Ok(())
+    }
+
+    const TEST_MIGRATIONS: &[Migration] = &[unversioned, rename_name];
+
+    #[test]
+    fn records_are_upgraded_to_the_current_version() {
+        let mut legacy = json!({ "name": "Daily standup" });
+        upgrade(&mut legacy, TEST_MIGRATIONS).unwrap();
+        assert_eq!(legacy, json!({ "title": "Daily standup", "schema_version": 2 }));
+
+        // Already current: no migration runs again
+        let mut current = json!({ "schema_version": 2, "title": "Retro" });
+        upgrade(&mut current, TEST_MIGRATIONS).unwrap();
+        assert_eq!(current, json!({ "schema_version": 2, "title": "Retro" }));
+    }
+
+    #[test]
+    fn unreadable_versions_are_rejected() {
+        assert!(upgrade(&mut json!({ "schema_version": 3 }), TEST_MIGRATIONS).unwrap_err().contains("newer"));
+        assert!(upgrade(&mut json!({ "schema_version": "2" }), TEST_MIGRATIONS).is_err());
+        assert!(upgrade(&mut json!(["not", "an", "object"]), TEST_MIGRATIONS).is_err());
+        let failed = upgrade(&mut json!({ "schema_version": 1 }), TEST_MIGRATIONS).unwrap_err();
+        assert!(failed.contains("migration from schema version 1"), "{}", failed);
+    }
+
+    #[test]
+    fn unreadable_files_are_quarantined_not_replaced() {
+        let dir = std::env::temp_dir().join(format!("deskwork-schema-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let _ = QUARANTINE_PATH.set(dir.join(QUARANTINE_DIR));
+        let parse = |value: Value| parse::<serde_json::Map<String, Value>>(value, TEST_MIGRATIONS);
+
+        assert!(load_file(&dir.join("missing.json"), parse).is_none());
+
+        let broken = dir.join("templates.json");
+        fs::write(&broken, "{ not json").unwrap();
+        assert!(load_file(&broken, parse).is_none());
+        assert!(!broken.exists());
+        let warning = get_storage_warnings()
+            .into_iter()
+            .find(|w| w.file == broken.display().to_string())
+            .expect("warning recorded");
+        let moved = PathBuf::from(warning.quarantined_to.expect("file was moved"));
+        assert_eq!(fs::read_to_string(moved).unwrap(), "{ not json");
+        assert!(get_storage_warnings().iter().all(|w| !w.file.ends_with("missing.json")));
+        assert!(ensure_writable(&broken).is_ok());
+
+        // With nowhere to move it, the file stays and saves over it are refused
+        let stuck = dir.join("settings.json");
+        fs::write(&stuck, "{ not json").unwrap();
+        assert!(quarantine_into(None, &stuck, "expected value").is_none());
+        assert_eq!(fs::read_to_string(&stuck).unwrap(), "{ not json");
+        assert!(ensure_writable(&stuck).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
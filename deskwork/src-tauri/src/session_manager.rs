@@ -1,11 +1,24 @@
+use rusqlite::{params, Connection, OptionalExtension, Transaction, TransactionBehavior};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
-use std::sync::Mutex;
-use crate::agent::Message;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::Manager;
+use crate::agent::{AgentMode, AgentState, ApprovalState, Message, MessageContent};
+use crate::skills::SkillState;
+use crate::policy::PolicyState;
+use crate::schema::{self, Migration};
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+// The database schema is versioned separately (`PRAGMA user_version`); these cover the JSON
+// form of a session in legacy files and exports.
+const MIGRATIONS: &[Migration] = &[schema::unversioned];
+pub const SCHEMA_VERSION: u32 = schema::current_version(MIGRATIONS);
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct Session {
+    #[serde(default)]
+    pub schema_version: u32,
     pub id: String,
     pub title: String,
     pub messages: Vec<Message>,
@@ -13,6 +26,72 @@ pub struct Session {
     pub updated_at: u64,
     #[serde(default)]
     pub pinned: bool,
+    #[serde(default)]
+    pub privacy: PrivacyLevel,
+    // Set on forks: the session they branched from and the parent message they branch after
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forked_at: Option<usize>,
+    // Rolling summary kept up to date by the agent; `summarized_through` is the message count it covers
+    #[serde(default)]
+    pub summary: String,
+    #[serde(default)]
+    pub summarized_through: usize,
+    // Archived sessions are hidden from `list_sessions` unless asked for
+    #[serde(default)]
+    pub archived: bool,
+    // Workspace, mode, model and skills the session runs with; `chat` falls back to the
+    // global defaults for any that are unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<AgentMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled_skills: Option<Vec<String>>,
+}
+
+pub const DEFAULT_TITLE: &str = "New Chat";
+// Titles that were never chosen by the user ("Session" was written by older builds on every turn)
+const PLACEHOLDER_TITLES: &[&str] = &[DEFAULT_TITLE, "Untitled Chat", "Session"];
+
+pub fn has_placeholder_title(session: &Session) -> bool {
+    PLACEHOLDER_TITLES.contains(&session.title.as_str())
+}
+
+// How much of a session survives `export_sessions`. Storage is always lossless (and sealed by
+// the vault when session encryption is on); the level only decides what leaves the machine.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PrivacyLevel {
+    // Secrets are redacted; tool calls, tool output and screenshots are kept
+    #[default]
+    Standard,
+    // Tool arguments, tool output and images are replaced with placeholders as well
+    Strict,
+    // Exported verbatim
+    Open,
+}
+
+impl PrivacyLevel {
+    fn as_str(self) -> &'static str {
+        match self {
+            PrivacyLevel::Standard => "standard",
+            PrivacyLevel::Strict => "strict",
+            PrivacyLevel::Open => "open",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "standard" => Some(PrivacyLevel::Standard),
+            "strict" => Some(PrivacyLevel::Strict),
+            "open" => Some(PrivacyLevel::Open),
+            _ => None,
+        }
+    }
 }
 
 pub struct SessionState {
@@ -27,54 +106,340 @@ impl Default for SessionState {
     }
 }
 
+// Relative to the process CWD; used by earlier versions and migrated on first launch
+const LEGACY_SESSIONS_DIR: &str = ".deskwork/sessions";
+
+const DB_FILE: &str = "sessions.db";
+// JSON session files are imported into the database once and then moved here
+const IMPORTED_JSON_DIR: &str = "legacy-json";
+const SEARCH_LIMIT: usize = 200;
+
+static SESSIONS_DIR: OnceLock<PathBuf> = OnceLock::new();
+static DB: OnceLock<Mutex<Connection>> = OnceLock::new();
+// Serializes read-modify-write updates per session so a chat save cannot drop a concurrent rename or pin
+static SESSION_LOCKS: OnceLock<Mutex<HashMap<String, Arc<Mutex<()>>>>> = OnceLock::new();
+
+// `sessions.messages` is the JSON transcript, sealed by the vault when session encryption is on.
+// `messages` holds the searchable text of user and assistant messages (tool output is never
+// indexed, and nothing is while encryption is on); `messages_fts` indexes it as external content
+// and is kept in sync by triggers.
+const SCHEMA: &str = r#"
+PRAGMA journal_mode = WAL;
+CREATE TABLE IF NOT EXISTS sessions (
+    id TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL,
+    pinned INTEGER NOT NULL DEFAULT 0,
+    privacy TEXT NOT NULL DEFAULT 'standard',
+    parent_id TEXT,
+    forked_at INTEGER,
+    summary TEXT NOT NULL DEFAULT '',
+    summarized_through INTEGER NOT NULL DEFAULT 0,
+    archived INTEGER NOT NULL DEFAULT 0,
+    working_dir TEXT,
+    mode TEXT,
+    model TEXT,
+    enabled_skills TEXT,
+    messages TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS messages (
+    id INTEGER PRIMARY KEY,
+    session_id TEXT NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    message_index INTEGER NOT NULL,
+    role TEXT NOT NULL,
+    content TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS messages_session ON messages(session_id);
+CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(content, content='messages', content_rowid='id');
+CREATE TRIGGER IF NOT EXISTS messages_ai AFTER INSERT ON messages BEGIN
+    INSERT INTO messages_fts(rowid, content) VALUES (new.id, new.content);
+END;
+CREATE TRIGGER IF NOT EXISTS messages_ad AFTER DELETE ON messages BEGIN
+    INSERT INTO messages_fts(messages_fts, rowid, content) VALUES ('delete', old.id, old.content);
+END;
+"#;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SessionSearchHit {
+    pub session_id: String,
+    pub title: String,
+    // None when the title, id or summary matched rather than a message
+    pub message_index: Option<usize>,
+    pub role: Option<String>,
+    pub snippet: String,
+    pub updated_at: u64,
+    pub pinned: bool,
+}
+
+// Resolves `<app_data_dir>/sessions` and moves over any sessions left in the legacy location.
+// Sessions already present in the new dir are kept; the legacy copy is left in place.
+pub fn init(app_handle: &tauri::AppHandle) {
+    let Ok(data_dir) = app_handle.path().app_data_dir() else {
+        return;
+    };
+    let dir = data_dir.join("sessions");
+    let _ = fs::create_dir_all(&dir);
+    let legacy = Path::new(LEGACY_SESSIONS_DIR);
+    if let Ok(entries) = fs::read_dir(legacy) {
+        for entry in entries.flatten() {
+            let from = entry.path();
+            let Some(name) = from.file_name() else {
+                continue;
+            };
+            let to = dir.join(name);
+            if from.extension().is_some_and(|ext| ext == "json") && !to.exists() {
+                if let Err(e) = crate::settings::move_legacy_file(&from, &to) {
+                    crate::schema::warn(&from, &format!("could not move it to {}: {}", to.display(), e));
+                }
+            }
+        }
+        // Only removes the legacy dir once it is empty
+        let _ = fs::remove_dir(legacy);
+    }
+    let _ = SESSIONS_DIR.set(dir.clone());
+    if let Err(e) = import_json_sessions(&dir) {
+        crate::schema::warn(&dir, &format!("could not import JSON sessions: {}", e));
+    }
+    if crate::vault::enabled() {
+        if let Err(e) = clear_message_index() {
+            crate::schema::warn(&dir, &format!("could not clear the message search index: {}", e));
+        }
+    }
+}
+
+// Drops the plaintext message text once encryption is turned on. `secure_delete` overwrites
+// the freed pages so the text does not linger in the database file.
+pub fn clear_message_index() -> Result<(), String> {
+    with_db(|conn| {
+        conn.execute_batch(
+            "PRAGMA secure_delete = ON;
+             DELETE FROM messages;
+             INSERT INTO messages_fts(messages_fts) VALUES ('optimize');",
+        )
+    })
+}
+
+fn open_db(path: &Path) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+    conn.execute_batch(SCHEMA)?;
+    migrate_db(&conn)?;
+    Ok(conn)
+}
+
+// Brings databases created by older builds up to `SCHEMA`, tracked with `PRAGMA user_version`.
+fn migrate_db(conn: &Connection) -> rusqlite::Result<()> {
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version < 1 {
+        add_column_if_missing(conn, "privacy", "TEXT NOT NULL DEFAULT 'standard'")?;
+        conn.execute_batch("PRAGMA user_version = 1;")?;
+    }
+    if version < 2 {
+        add_column_if_missing(conn, "parent_id", "TEXT")?;
+        add_column_if_missing(conn, "forked_at", "INTEGER")?;
+        conn.execute_batch("PRAGMA user_version = 2;")?;
+    }
+    if version < 3 {
+        add_column_if_missing(conn, "summary", "TEXT NOT NULL DEFAULT ''")?;
+        add_column_if_missing(conn, "summarized_through", "INTEGER NOT NULL DEFAULT 0")?;
+        conn.execute_batch("PRAGMA user_version = 3;")?;
+    }
+    if version < 4 {
+        add_column_if_missing(conn, "archived", "INTEGER NOT NULL DEFAULT 0")?;
+        conn.execute_batch("PRAGMA user_version = 4;")?;
+    }
+    if version < 5 {
+        add_column_if_missing(conn, "working_dir", "TEXT")?;
+        add_column_if_missing(conn, "mode", "TEXT")?;
+        add_column_if_missing(conn, "model", "TEXT")?;
+        add_column_if_missing(conn, "enabled_skills", "TEXT")?;
+        conn.execute_batch("PRAGMA user_version = 5;")?;
+    }
+    Ok(())
+}
+
+// Fresh databases already have every column from `SCHEMA`.
+fn add_column_if_missing(conn: &Connection, column: &str, decl: &str) -> rusqlite::Result<()> {
+    let exists = conn
+        .prepare("SELECT 1 FROM pragma_table_info('sessions') WHERE name = ?1")?
+        .exists(params![column])?;
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE sessions ADD COLUMN {} {};", column, decl))?;
+    }
+    Ok(())
+}
+
+fn db() -> Result<&'static Mutex<Connection>, String> {
+    if let Some(db) = DB.get() {
+        return Ok(db);
+    }
+    let conn = open_db(&get_sessions_dir().join(DB_FILE)).map_err(|e| format!("Failed to open session store: {}", e))?;
+    Ok(DB.get_or_init(|| Mutex::new(conn)))
+}
+
+fn with_db<T>(f: impl FnOnce(&mut Connection) -> rusqlite::Result<T>) -> Result<T, String> {
+    let mut conn = db()?.lock().map_err(|e| e.to_string())?;
+    f(&mut conn).map_err(|e| e.to_string())
+}
+
+// One-time migration of the `<id>.json` files used before the database existed.
+// Imported files are moved to `legacy-json/`; files that fail to parse are quarantined.
+fn import_json_sessions(dir: &Path) -> Result<usize, String> {
+    let mut imported = 0;
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(0);
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_file() || path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let Some(session) = schema::load_file(&path, |value| schema::parse::<Session>(value, MIGRATIONS)) else {
+            continue;
+        };
+        // A session already in the database is newer than its JSON copy
+        let exists = with_db(|conn| {
+            conn.query_row("SELECT 1 FROM sessions WHERE id = ?1", params![session.id], |_| Ok(()))
+                .optional()
+                .map(|row| row.is_some())
+        })?;
+        if !exists {
+            save_session_to_disk(&session)?;
+            imported += 1;
+        }
+        if let Some(name) = path.file_name() {
+            let _ = crate::settings::move_legacy_file(&path, &dir.join(IMPORTED_JSON_DIR).join(name));
+        }
+    }
+    Ok(imported)
+}
+
+// Text indexed for search: plain content and the text parts of user/assistant messages.
+fn message_text(message: &Message) -> String {
+    if message.role == "tool" {
+        return String::new();
+    }
+    match &message.content {
+        Some(MessageContent::Text(text)) => text.clone(),
+        Some(MessageContent::Parts(parts)) => parts
+            .iter()
+            .filter_map(|p| p.text.as_deref())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        None => String::new(),
+    }
+}
+
+fn conversion_error(row: &rusqlite::Row<'_>, column: &str, e: Box<dyn std::error::Error + Send + Sync>) -> rusqlite::Error {
+    let index = row.as_ref().column_index(column).unwrap_or_default();
+    rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, e)
+}
+
+fn mode_to_str(mode: &AgentMode) -> &'static str {
+    match mode {
+        AgentMode::Plan => "plan",
+        AgentMode::Build => "build",
+    }
+}
+
+fn mode_from_str(value: &str) -> Option<AgentMode> {
+    match value {
+        "plan" => Some(AgentMode::Plan),
+        "build" => Some(AgentMode::Build),
+        _ => None,
+    }
+}
+
+fn session_from_row(row: &rusqlite::Row<'_>, with_messages: bool) -> rusqlite::Result<Session> {
+    let messages = if with_messages {
+        let stored: String = row.get("messages")?;
+        let json = crate::vault::open(&stored).map_err(|e| conversion_error(row, "messages", e.into()))?;
+        serde_json::from_str(&json).map_err(|e| conversion_error(row, "messages", Box::new(e)))?
+    } else {
+        Vec::new()
+    };
+    let enabled_skills = match row.get::<_, Option<String>>("enabled_skills")? {
+        Some(json) => Some(serde_json::from_str(&json).map_err(|e| conversion_error(row, "enabled_skills", Box::new(e)))?),
+        None => None,
+    };
+    Ok(Session {
+        schema_version: SCHEMA_VERSION,
+        id: row.get("id")?,
+        title: row.get("title")?,
+        messages,
+        created_at: row.get::<_, i64>("created_at")? as u64,
+        updated_at: row.get::<_, i64>("updated_at")? as u64,
+        pinned: row.get("pinned")?,
+        privacy: PrivacyLevel::parse(&row.get::<_, String>("privacy")?).unwrap_or_default(),
+        parent_id: row.get("parent_id")?,
+        forked_at: row.get::<_, Option<i64>>("forked_at")?.map(|i| i as usize),
+        summary: row.get("summary")?,
+        summarized_through: row.get::<_, i64>("summarized_through")? as usize,
+        archived: row.get("archived")?,
+        working_dir: row.get("working_dir")?,
+        mode: row.get::<_, Option<String>>("mode")?.as_deref().and_then(mode_from_str),
+        model: row.get("model")?,
+        enabled_skills,
+    })
+}
+
+const METADATA_COLUMNS: &str = "id, title, created_at, updated_at, pinned, privacy, parent_id, forked_at, summary, \
+    summarized_through, archived, working_dir, mode, model, enabled_skills";
+const SESSION_COLUMNS: &str = "id, title, created_at, updated_at, pinned, privacy, parent_id, forked_at, summary, \
+    summarized_through, archived, working_dir, mode, model, enabled_skills, messages";
+
 // Helper to get storage path
 pub fn get_sessions_dir() -> PathBuf {
-    // In a real app, use dirs::data_dir() or similar. 
-    // For this MVP, we use a local .deskwork/sessions folder.
-    let path = PathBuf::from(".deskwork/sessions");
+    let path = SESSIONS_DIR
+        .get()
+        .cloned()
+        .unwrap_or_else(|| PathBuf::from(LEGACY_SESSIONS_DIR));
     if !path.exists() {
         let _ = fs::create_dir_all(&path);
     }
     path
 }
 
-#[tauri::command]
-pub fn list_sessions() -> Result<Vec<Session>, String> {
-    let dir = get_sessions_dir();
-    let mut sessions = Vec::new();
+// Points the store at a per-process temp dir so tests never touch real sessions
+#[cfg(test)]
+pub(crate) fn use_test_store() {
+    let dir = std::env::temp_dir().join(format!("deskwork-sessions-test-{}", std::process::id()));
+    let _ = SESSIONS_DIR.set(dir);
+}
 
-    if let Ok(entries) = fs::read_dir(dir) {
-        for entry in entries.flatten() {
-            if let Ok(content) = fs::read_to_string(entry.path()) {
-                if let Ok(session) = serde_json::from_str::<Session>(&content) {
-                    sessions.push(session);
-                }
-            }
-        }
-    }
-    // Sort by updated_at desc
-    sessions.sort_by(|a, b| {
-        match (b.pinned, a.pinned) {
-            (true, false) => std::cmp::Ordering::Less,
-            (false, true) => std::cmp::Ordering::Greater,
-            _ => b.updated_at.cmp(&a.updated_at),
-        }
-    });
-    Ok(sessions)
+// Session metadata only (`messages` is empty); `switch_session` loads the full record.
+#[tauri::command]
+pub fn list_sessions(include_archived: Option<bool>) -> Result<Vec<Session>, String> {
+    let include_archived = include_archived.unwrap_or(false);
+    with_db(|conn| {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM sessions WHERE ?1 OR archived = 0 ORDER BY pinned DESC, updated_at DESC",
+            METADATA_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![include_archived], |row| session_from_row(row, false))?;
+        rows.collect()
+    })
 }
 
+// The new session is bound to the current mode, so switching back to it restores that mode.
 #[tauri::command]
-pub fn create_session(title: String, state: tauri::State<'_, SessionState>) -> Result<Session, String> {
+pub fn create_session(
+    title: String,
+    state: tauri::State<'_, SessionState>,
+    agent_state: tauri::State<'_, AgentState>,
+) -> Result<Session, String> {
     let id = uuid::Uuid::new_v4().to_string();
     let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
     
     let session = Session {
         id: id.clone(),
-        title: if title.is_empty() { "New Chat".to_string() } else { title },
+        title: if title.is_empty() { DEFAULT_TITLE.to_string() } else { title },
         messages: Vec::new(),
         created_at: now,
         updated_at: now,
-        pinned: false,
+        mode: Some(agent_state.mode.lock().map_err(|e| e.to_string())?.clone()),
+        ..Default::default()
     };
 
     save_session_to_disk(&session)?;
@@ -84,46 +449,248 @@ pub fn create_session(title: String, state: tauri::State<'_, SessionState>) -> R
     Ok(session)
 }
 
+// Also restores the session's mode and skill toggles so `get_agent_mode`/`list_skills` reflect it;
+// a session without a bound mode (created before modes were bound) gets the default rather than
+// whatever the previous session used. The returned record carries its working dir and model for the UI.
 #[tauri::command]
-pub fn switch_session(session_id: String, state: tauri::State<'_, SessionState>) -> Result<Session, String> {
-    let dir = get_sessions_dir();
-    let path = dir.join(format!("{}.json", session_id));
-    
-    if !path.exists() {
-        return Err("Session not found".to_string());
-    }
-
-    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
-    let session: Session = serde_json::from_str(&content).map_err(|e| e.to_string())?;
-
+pub fn switch_session(
+    session_id: String,
+    state: tauri::State<'_, SessionState>,
+    agent_state: tauri::State<'_, AgentState>,
+    skill_state: tauri::State<'_, SkillState>,
+) -> Result<Session, String> {
+    let session = load_session(&session_id)?;
     *state.current_session_id.lock().map_err(|e| e.to_string())? = Some(session_id);
-
+    *agent_state.mode.lock().map_err(|e| e.to_string())? = session.mode.clone().unwrap_or_default();
+    crate::skills::apply_session_skills(&skill_state, session.enabled_skills.as_deref())?;
     Ok(session)
 }
 
-pub fn save_session_to_disk(session: &Session) -> Result<(), String> {
-    let dir = get_sessions_dir();
-    let path = dir.join(format!("{}.json", session.id));
-    let json = serde_json::to_string_pretty(session).map_err(|e| e.to_string())?;
-    fs::write(path, json).map_err(|e| e.to_string())?;
+// Binds the session to a model; None (or empty) follows the model in settings again.
+#[tauri::command]
+pub fn set_session_model(session_id: String, model: Option<String>) -> Result<Session, String> {
+    update_session(&session_id, |session| {
+        session.model = model.filter(|m| !m.trim().is_empty());
+    })
+}
+
+// Saves a new session holding `messages` from `parent` and makes it the current session.
+fn create_fork(
+    parent: &Session,
+    messages: Vec<Message>,
+    forked_at: usize,
+    suffix: &str,
+    state: &SessionState,
+) -> Result<Session, String> {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    let fork = Session {
+        id: uuid::Uuid::new_v4().to_string(),
+        title: format!("{} ({})", parent.title, suffix),
+        messages,
+        created_at: now,
+        updated_at: now,
+        pinned: false,
+        privacy: parent.privacy,
+        parent_id: Some(parent.id.clone()),
+        forked_at: Some(forked_at),
+        working_dir: parent.working_dir.clone(),
+        mode: parent.mode.clone(),
+        model: parent.model.clone(),
+        enabled_skills: parent.enabled_skills.clone(),
+        // The parent's summary may cover messages the fork does not have
+        ..Default::default()
+    };
+    save_session_to_disk(&fork)?;
+    *state.current_session_id.lock().map_err(|e| e.to_string())? = Some(fork.id.clone());
+    Ok(fork)
+}
+
+// Copies the history up to and including `message_index` into a new session. Tool results
+// answering calls made at that message are carried over so the history stays valid.
+#[tauri::command]
+pub fn fork_session(session_id: String, message_index: usize, state: tauri::State<'_, SessionState>) -> Result<Session, String> {
+    let parent = load_session(&session_id)?;
+    if message_index >= parent.messages.len() {
+        return Err(format!("Message {} does not exist in this session", message_index));
+    }
+    let mut end = message_index + 1;
+    while parent.messages.get(end).is_some_and(|m| m.role == "tool") {
+        end += 1;
+    }
+    create_fork(&parent, parent.messages[..end].to_vec(), message_index, "fork", &state)
+}
+
+// Edit-and-resubmit: creates a sibling branch holding the history before the user prompt at
+// `message_index`. Sending the edited prompt with `chat` then continues the branch while the
+// original attempt stays untouched in the parent.
+#[tauri::command]
+pub fn branch_for_edit(session_id: String, message_index: usize, state: tauri::State<'_, SessionState>) -> Result<Session, String> {
+    let parent = load_session(&session_id)?;
+    match parent.messages.get(message_index) {
+        Some(message) if message.role == "user" => {}
+        Some(_) => return Err("Only user prompts can be edited".to_string()),
+        None => return Err(format!("Message {} does not exist in this session", message_index)),
+    }
+    create_fork(&parent, parent.messages[..message_index].to_vec(), message_index, "edit", &state)
+}
+
+fn session_lock(session_id: &str) -> Arc<Mutex<()>> {
+    let locks = SESSION_LOCKS.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut locks = locks.lock().unwrap_or_else(|e| e.into_inner());
+    locks.entry(session_id.to_string()).or_default().clone()
+}
+
+// Upserts the session row and re-indexes its message text within the caller's transaction.
+fn write_session(tx: &Transaction<'_>, session: &Session) -> rusqlite::Result<()> {
+    let json = serde_json::to_string(&session.messages)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    let messages = crate::vault::seal(&json).map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
+    let enabled_skills = match &session.enabled_skills {
+        Some(skills) => Some(serde_json::to_string(skills).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?),
+        None => None,
+    };
+    tx.execute(
+        "INSERT INTO sessions (id, title, created_at, updated_at, pinned, privacy, parent_id, forked_at,
+             summary, summarized_through, archived, working_dir, mode, model, enabled_skills, messages)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+         ON CONFLICT(id) DO UPDATE SET title = excluded.title, created_at = excluded.created_at,
+             updated_at = excluded.updated_at, pinned = excluded.pinned, privacy = excluded.privacy,
+             parent_id = excluded.parent_id, forked_at = excluded.forked_at, summary = excluded.summary,
+             summarized_through = excluded.summarized_through, archived = excluded.archived,
+             working_dir = excluded.working_dir, mode = excluded.mode, model = excluded.model,
+             enabled_skills = excluded.enabled_skills, messages = excluded.messages",
+        params![
+            session.id,
+            session.title,
+            session.created_at as i64,
+            session.updated_at as i64,
+            session.pinned,
+            session.privacy.as_str(),
+            session.parent_id,
+            session.forked_at.map(|i| i as i64),
+            session.summary,
+            session.summarized_through as i64,
+            session.archived,
+            session.working_dir,
+            session.mode.as_ref().map(mode_to_str),
+            session.model,
+            enabled_skills,
+            messages
+        ],
+    )?;
+    tx.execute("DELETE FROM messages WHERE session_id = ?1", params![session.id])?;
+    // A plaintext index would defeat the sealed transcript
+    if crate::vault::enabled() {
+        return Ok(());
+    }
+    let mut insert = tx.prepare(
+        "INSERT INTO messages (session_id, message_index, role, content) VALUES (?1, ?2, ?3, ?4)",
+    )?;
+    for (index, message) in session.messages.iter().enumerate() {
+        let text = message_text(message);
+        if !text.trim().is_empty() {
+            insert.execute(params![session.id, index as i64, message.role, text])?;
+        }
+    }
     Ok(())
 }
 
-fn load_session(session_id: &str) -> Result<Session, String> {
-    let dir = get_sessions_dir();
-    let path = dir.join(format!("{}.json", session_id));
-    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
-    let session: Session = serde_json::from_str(&content).map_err(|e| e.to_string())?;
-    Ok(session)
+// Replaces the whole record; use `update_session` to change part of an existing one.
+pub fn save_session_to_disk(session: &Session) -> Result<(), String> {
+    let lock = session_lock(&session.id);
+    let _guard = lock.lock().map_err(|e| e.to_string())?;
+    with_db(|conn| {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        write_session(&tx, session)?;
+        tx.commit()
+    })
+}
+
+// Loads the stored record, applies `update` and writes it back atomically, holding the
+// session's lock throughout. Fields `update` leaves alone (title, pin, created_at) are kept.
+pub fn update_session(session_id: &str, update: impl FnOnce(&mut Session)) -> Result<Session, String> {
+    let lock = session_lock(session_id);
+    let _guard = lock.lock().map_err(|e| e.to_string())?;
+    let sql = format!("SELECT {} FROM sessions WHERE id = ?1", SESSION_COLUMNS);
+    with_db(|conn| {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let session = tx.query_row(&sql, params![session_id], |row| session_from_row(row, true)).optional()?;
+        let Some(mut session) = session else {
+            return Ok(None);
+        };
+        update(&mut session);
+        write_session(&tx, &session)?;
+        tx.commit()?;
+        Ok(Some(session))
+    })?
+    .ok_or_else(|| "Session not found".to_string())
+}
+
+pub fn load_session(session_id: &str) -> Result<Session, String> {
+    let sql = format!("SELECT {} FROM sessions WHERE id = ?1", SESSION_COLUMNS);
+    with_db(|conn| conn.query_row(&sql, params![session_id], |row| session_from_row(row, true)).optional())?
+        .ok_or_else(|| "Session not found".to_string())
 }
 
-fn sanitize_messages(messages: &[Message]) -> Vec<Message> {
+// Like `load_session` without decrypting the transcript.
+pub fn load_session_metadata(session_id: &str) -> Result<Session, String> {
+    let sql = format!("SELECT {} FROM sessions WHERE id = ?1", METADATA_COLUMNS);
+    with_db(|conn| conn.query_row(&sql, params![session_id], |row| session_from_row(row, false)).optional())?
+        .ok_or_else(|| "Session not found".to_string())
+}
+
+fn load_all_sessions() -> Result<Vec<Session>, String> {
+    let sql = format!("SELECT {} FROM sessions ORDER BY pinned DESC, updated_at DESC", SESSION_COLUMNS);
+    with_db(|conn| {
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map([], |row| session_from_row(row, true))?;
+        rows.collect()
+    })
+}
+
+const TOOL_OUTPUT_PLACEHOLDER: &str = "[redacted tool output]";
+const IMAGE_PLACEHOLDER: &str = "[redacted image]";
+
+// Applies the session's privacy level to a copy of its transcript for export.
+pub(crate) fn redact_for_export(messages: &[Message], level: PrivacyLevel) -> Vec<Message> {
+    if level == PrivacyLevel::Open {
+        return messages.to_vec();
+    }
+    let strict = level == PrivacyLevel::Strict;
     messages
         .iter()
         .map(|m| {
             let mut clone = m.clone();
-            if clone.role == "tool" {
-                clone.content = Some(crate::agent::MessageContent::Text("[redacted tool output]".into()));
+            clone.content = match clone.content.take() {
+                _ if strict && clone.role == "tool" => Some(MessageContent::Text(TOOL_OUTPUT_PLACEHOLDER.into())),
+                // Inline screenshots are kept (or dropped in strict mode), the text around them is redacted
+                Some(MessageContent::Text(text)) => Some(MessageContent::Text(
+                    crate::redact::redact_around_images(&text, strict.then_some(IMAGE_PLACEHOLDER)),
+                )),
+                Some(MessageContent::Parts(parts)) => Some(MessageContent::Parts(
+                    parts
+                        .into_iter()
+                        .map(|mut part| {
+                            part.text = part.text.map(|t| crate::redact::redact_around_images(&t, strict.then_some(IMAGE_PLACEHOLDER)));
+                            if strict && part.image_url.is_some() {
+                                part.r#type = "text".into();
+                                part.image_url = None;
+                                part.text = Some(IMAGE_PLACEHOLDER.into());
+                            }
+                            part
+                        })
+                        .collect(),
+                )),
+                None => None,
+            };
+            if let Some(calls) = clone.tool_calls.as_mut() {
+                for call in calls {
+                    call.function.arguments = if strict {
+                        "{}".into()
+                    } else {
+                        crate::redact::redact(&call.function.arguments)
+                    };
+                }
             }
             clone
         })
@@ -132,62 +699,481 @@ fn sanitize_messages(messages: &[Message]) -> Vec<Message> {
 
 #[tauri::command]
 pub fn rename_session(session_id: String, title: String) -> Result<Session, String> {
-    let mut session = load_session(&session_id)?;
-    session.title = if title.is_empty() { "Untitled Chat".into() } else { title };
-    session.updated_at = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
-    save_session_to_disk(&session)?;
-    Ok(session)
+    // A placeholder title would let the auto-titler or an import overwrite the user's choice
+    if title.trim().is_empty() {
+        return Err("Title cannot be empty".to_string());
+    }
+    update_session(&session_id, |session| {
+        session.title = title;
+        session.updated_at = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    })
+}
+
+#[tauri::command]
+pub fn set_session_privacy(session_id: String, privacy: PrivacyLevel) -> Result<Session, String> {
+    update_session(&session_id, |session| {
+        session.privacy = privacy;
+    })
 }
 
 #[tauri::command]
 pub fn toggle_pin(session_id: String, pinned: bool) -> Result<Session, String> {
-    let mut session = load_session(&session_id)?;
-    session.pinned = pinned;
-    session.updated_at = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
-    save_session_to_disk(&session)?;
-    Ok(session)
+    update_session(&session_id, |session| {
+        session.pinned = pinned;
+        session.updated_at = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    })
+}
+
+// Outcome of one session in a bulk operation; failures do not stop the rest.
+#[derive(Serialize, Clone, Debug)]
+pub struct SessionOpResult {
+    pub session_id: String,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+fn bulk(session_ids: Vec<String>, mut op: impl FnMut(&str) -> Result<(), String>) -> Vec<SessionOpResult> {
+    session_ids
+        .into_iter()
+        .map(|session_id| {
+            let error = op(&session_id).err();
+            SessionOpResult { session_id, ok: error.is_none(), error }
+        })
+        .collect()
+}
+
+// Removes the record (its search index rows cascade), its log files and everything the
+// agent keeps to resume it: pending approvals, suspended turns, grants and session policy rules.
+fn remove_session(app: &tauri::AppHandle, session_id: &str) -> Result<(), String> {
+    let lock = session_lock(session_id);
+    let _guard = lock.lock().map_err(|e| e.to_string())?;
+    let deleted = with_db(|conn| conn.execute("DELETE FROM sessions WHERE id = ?1", params![session_id]))?;
+    if deleted == 0 {
+        return Err("Session not found".to_string());
+    }
+    crate::logging::clear_logs(Some(session_id.to_string()))?;
+    if let Some(approvals) = app.try_state::<ApprovalState>() {
+        crate::agent::forget_session(&approvals, session_id);
+    }
+    if let Some(policy) = app.try_state::<PolicyState>() {
+        policy.session_rules.lock().map_err(|e| e.to_string())?.remove(session_id);
+    }
+    if let Some(state) = app.try_state::<SessionState>() {
+        let mut current = state.current_session_id.lock().map_err(|e| e.to_string())?;
+        if current.as_deref() == Some(session_id) {
+            *current = None;
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn delete_session(app: tauri::AppHandle, session_id: String) -> Result<(), String> {
+    remove_session(&app, &session_id)
+}
+
+#[tauri::command]
+pub fn delete_sessions(app: tauri::AppHandle, session_ids: Vec<String>) -> Vec<SessionOpResult> {
+    bulk(session_ids, |id| remove_session(&app, id))
 }
 
 #[tauri::command]
-pub fn search_sessions(query: String) -> Result<Vec<Session>, String> {
-    let q = query.to_lowercase();
-    let mut sessions = list_sessions()?;
-    if !q.is_empty() {
-        sessions.retain(|s| s.title.to_lowercase().contains(&q) || s.id.to_lowercase().contains(&q));
-    }
-    // pinned first, then updated_at desc
-    sessions.sort_by(|a, b| {
-        match (b.pinned, a.pinned) {
-            (true, false) => std::cmp::Ordering::Less,
-            (false, true) => std::cmp::Ordering::Greater,
-            _ => b.updated_at.cmp(&a.updated_at),
+pub fn archive_session(session_id: String, archived: bool) -> Result<Session, String> {
+    update_session(&session_id, |session| {
+        session.archived = archived;
+    })
+}
+
+#[tauri::command]
+pub fn archive_sessions(session_ids: Vec<String>, archived: bool) -> Vec<SessionOpResult> {
+    bulk(session_ids, |id| update_session(id, |session| session.archived = archived).map(|_| ()))
+}
+
+// Deletes unpinned sessions not updated for `retention_days` (0 keeps everything) and
+// returns their ids. Runs at startup with `session_retention_days`.
+pub fn apply_retention(app: &tauri::AppHandle, retention_days: u64) -> Result<Vec<String>, String> {
+    if retention_days == 0 {
+        return Ok(Vec::new());
+    }
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    let cutoff = now.saturating_sub(retention_days * 24 * 60 * 60);
+    let expired: Vec<String> = with_db(|conn| {
+        let mut stmt = conn.prepare("SELECT id FROM sessions WHERE pinned = 0 AND updated_at < ?1")?;
+        let rows = stmt.query_map(params![cutoff as i64], |row| row.get(0))?;
+        rows.collect()
+    })?;
+    let mut removed = Vec::new();
+    for id in expired {
+        match remove_session(app, &id) {
+            Ok(()) => removed.push(id),
+            Err(e) => tracing::warn!("sessions: retention could not delete {}: {}", id, e),
         }
-    });
-    Ok(sessions)
+    }
+    Ok(removed)
+}
+
+#[tauri::command]
+pub fn apply_session_retention(
+    app: tauri::AppHandle,
+    settings: tauri::State<'_, crate::settings::SettingsState>,
+) -> Result<Vec<String>, String> {
+    let retention_days = settings.0.lock().map_err(|e| e.to_string())?.session_retention_days;
+    apply_retention(&app, retention_days)
+}
+
+// Each term is quoted (so FTS5 syntax in user input is literal) and prefix-matched.
+fn fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Title/id/summary matches first, then message matches ranked by relevance, pinned sessions ahead.
+// An empty query lists every session.
+#[tauri::command]
+pub fn search_sessions(query: String) -> Result<Vec<SessionSearchHit>, String> {
+    let q = query.trim().to_lowercase();
+    let fts = fts_query(&q);
+    with_db(|conn| {
+        let mut hits = Vec::new();
+        let mut stmt = conn.prepare(
+            "SELECT id, title, updated_at, pinned, summary FROM sessions
+             WHERE ?1 = '' OR instr(lower(title), ?1) > 0 OR instr(lower(id), ?1) > 0 OR instr(lower(summary), ?1) > 0
+             ORDER BY pinned DESC, updated_at DESC",
+        )?;
+        let rows = stmt.query_map(params![q], |row| {
+            let title: String = row.get(1)?;
+            let summary: String = row.get(4)?;
+            Ok(SessionSearchHit {
+                session_id: row.get(0)?,
+                snippet: if summary.is_empty() { title.clone() } else { summary },
+                title,
+                message_index: None,
+                role: None,
+                updated_at: row.get::<_, i64>(2)? as u64,
+                pinned: row.get(3)?,
+            })
+        })?;
+        for hit in rows {
+            hits.push(hit?);
+        }
+        if fts.is_empty() {
+            return Ok(hits);
+        }
+        let mut stmt = conn.prepare(
+            "SELECT m.session_id, s.title, m.message_index, m.role,
+                    snippet(messages_fts, 0, '**', '**', '…', 12), s.updated_at, s.pinned
+             FROM messages_fts
+             JOIN messages m ON m.id = messages_fts.rowid
+             JOIN sessions s ON s.id = m.session_id
+             WHERE messages_fts MATCH ?1
+             ORDER BY s.pinned DESC, rank
+             LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![fts, SEARCH_LIMIT as i64], |row| {
+            Ok(SessionSearchHit {
+                session_id: row.get(0)?,
+                title: row.get(1)?,
+                message_index: Some(row.get::<_, i64>(2)? as usize),
+                role: Some(row.get(3)?),
+                snippet: row.get(4)?,
+                updated_at: row.get::<_, i64>(5)? as u64,
+                pinned: row.get(6)?,
+            })
+        })?;
+        for hit in rows {
+            hits.push(hit?);
+        }
+        Ok(hits)
+    })
+}
+
+const EXPORT_FORMAT: &str = "deskwork.sessions";
+// Bumped when the exported `Session` shape changes incompatibly; a bare array is version 0
+const EXPORT_VERSION: u32 = 1;
+const MESSAGE_ROLES: &[&str] = &["system", "user", "assistant", "tool"];
+
+#[derive(Serialize)]
+struct SessionExport {
+    format: String,
+    version: u32,
+    exported_at: u64,
+    sessions: Vec<Session>,
+}
+
+// Sessions stay raw JSON until each is upgraded, so one bad record fails alone.
+#[derive(Deserialize)]
+struct ImportEnvelope {
+    format: String,
+    version: u32,
+    sessions: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ImportPayload {
+    Envelope(ImportEnvelope),
+    Legacy(Vec<serde_json::Value>),
+}
+
+// What to do when an imported session's id already exists
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportConflict {
+    #[default]
+    Skip,
+    // Import under a fresh id, leaving the existing session untouched
+    Rename,
+    // Keep whichever history extends the other; diverged histories are imported as a fork
+    Merge,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ImportResult {
+    // Id as found in the payload
+    pub source_id: String,
+    // Id the session was stored under, if it was stored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    // imported | skipped | renamed | merged | forked | failed
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
 #[tauri::command]
 pub fn export_sessions() -> Result<String, String> {
-    let sessions = list_sessions()?;
+    let sessions = load_all_sessions()?;
     let redacted: Vec<Session> = sessions
         .into_iter()
         .map(|mut s| {
-            s.messages = sanitize_messages(&s.messages);
+            s.messages = redact_for_export(&s.messages, s.privacy);
             s
         })
         .collect();
-    serde_json::to_string_pretty(&redacted).map_err(|e| e.to_string())
+    let export = SessionExport {
+        format: EXPORT_FORMAT.to_string(),
+        version: EXPORT_VERSION,
+        exported_at: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs(),
+        sessions: redacted,
+    };
+    serde_json::to_string_pretty(&export).map_err(|e| e.to_string())
 }
 
-#[tauri::command]
-pub fn import_sessions(payload: String) -> Result<usize, String> {
-    let imported: Vec<Session> = serde_json::from_str(&payload).map_err(|e| e.to_string())?;
-    let mut count = 0;
-    for mut session in imported {
-        session.messages = sanitize_messages(&session.messages);
-        save_session_to_disk(&session)?;
-        count += 1;
+// Ids become log file names and parent links, so only canonical UUIDs are accepted.
+fn validate_import(session: &mut Session) -> Result<(), String> {
+    let id = uuid::Uuid::parse_str(&session.id).map_err(|_| format!("'{}' is not a valid session id", session.id))?;
+    session.id = id.to_string();
+    if let Some(parent) = &session.parent_id {
+        match uuid::Uuid::parse_str(parent) {
+            Ok(parent) => session.parent_id = Some(parent.to_string()),
+            Err(_) => {
+                session.parent_id = None;
+                session.forked_at = None;
+            }
+        }
     }
-    Ok(count)
+    if let Some(m) = session.messages.iter().find(|m| !MESSAGE_ROLES.contains(&m.role.as_str())) {
+        return Err(format!("Unknown message role '{}'", m.role));
+    }
+    if session.title.trim().is_empty() {
+        session.title = DEFAULT_TITLE.to_string();
+    }
+    session.summarized_through = session.summarized_through.min(session.messages.len());
+    // The workspace bounds what the agent's path policy treats as in scope, so an export must not
+    // choose it; mode and skills fall back to the importer's own defaults
+    session.working_dir = None;
+    session.mode = None;
+    session.enabled_skills = None;
+    Ok(())
 }
 
+fn same_message(a: &Message, b: &Message) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+fn common_prefix(a: &[Message], b: &[Message]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| same_message(x, y)).count()
+}
+
+// Merges into the existing record when one history is a prefix of the other. Otherwise the
+// import becomes a fork of the existing session at the last shared message.
+// Exports are redacted, so the local history is compared in the form it would be exported in
+// today; a session whose privacy level changed since the export comes back as a fork.
+fn merge_import(imported: Session) -> Result<(String, &'static str), String> {
+    let mut diverged = None;
+    update_session(&imported.id, |existing| {
+        let exported = redact_for_export(&existing.messages, existing.privacy);
+        let shared = common_prefix(&exported, &imported.messages);
+        if shared < existing.messages.len() && shared < imported.messages.len() {
+            diverged = Some(shared);
+            return;
+        }
+        if imported.messages.len() > existing.messages.len() {
+            // The shared part keeps its unredacted local copy
+            let known = existing.messages.len();
+            existing.messages.extend_from_slice(&imported.messages[known..]);
+            existing.summary = imported.summary.clone();
+            existing.summarized_through = imported.summarized_through;
+        }
+        if has_placeholder_title(existing) {
+            existing.title = imported.title.clone();
+        }
+        existing.pinned |= imported.pinned;
+        existing.created_at = existing.created_at.min(imported.created_at);
+        existing.updated_at = existing.updated_at.max(imported.updated_at);
+    })?;
+    let Some(shared) = diverged else {
+        return Ok((imported.id, "merged"));
+    };
+    let fork = Session {
+        id: uuid::Uuid::new_v4().to_string(),
+        parent_id: Some(imported.id.clone()),
+        forked_at: shared.checked_sub(1),
+        ..imported
+    };
+    save_session_to_disk(&fork)?;
+    Ok((fork.id, "forked"))
+}
+
+fn import_one(mut session: Session, on_conflict: ImportConflict) -> Result<(String, &'static str), String> {
+    validate_import(&mut session)?;
+    let exists = with_db(|conn| {
+        conn.query_row("SELECT 1 FROM sessions WHERE id = ?1", params![session.id], |_| Ok(()))
+            .optional()
+            .map(|row| row.is_some())
+    })?;
+    if !exists {
+        save_session_to_disk(&session)?;
+        return Ok((session.id, "imported"));
+    }
+    match on_conflict {
+        ImportConflict::Skip => Ok((session.id, "skipped")),
+        ImportConflict::Rename => {
+            session.id = uuid::Uuid::new_v4().to_string();
+            save_session_to_disk(&session)?;
+            Ok((session.id, "renamed"))
+        }
+        ImportConflict::Merge => merge_import(session),
+    }
+}
+
+// Accepts the versioned export envelope or the bare array written by older builds. Exports are
+// already redacted to each session's privacy level, so transcripts are stored as given.
+#[tauri::command]
+pub fn import_sessions(payload: String, on_conflict: Option<ImportConflict>) -> Result<Vec<ImportResult>, String> {
+    let sessions = match serde_json::from_str::<ImportPayload>(&payload).map_err(|e| format!("Not a session export: {}", e))? {
+        ImportPayload::Envelope(export) => {
+            if export.format != EXPORT_FORMAT {
+                return Err(format!("Unknown export format '{}'", export.format));
+            }
+            if export.version > EXPORT_VERSION {
+                return Err(format!(
+                    "Export version {} is newer than this app supports ({})",
+                    export.version, EXPORT_VERSION
+                ));
+            }
+            export.sessions
+        }
+        ImportPayload::Legacy(sessions) => sessions,
+    };
+    let on_conflict = on_conflict.unwrap_or_default();
+    Ok(sessions
+        .into_iter()
+        .map(|record| {
+            let source_id = record["id"].as_str().unwrap_or_default().to_string();
+            let result = schema::parse::<Session>(record, MIGRATIONS).and_then(|session| import_one(session, on_conflict));
+            match result {
+                Ok((id, status)) => ImportResult { source_id, session_id: Some(id), status: status.to_string(), error: None },
+                Err(e) => ImportResult { source_id, session_id: None, status: "failed".to_string(), error: Some(e) },
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, text: &str) -> Message {
+        Message {
+            role: role.into(),
+            content: Some(MessageContent::Text(text.into())),
+            tool_calls: None,
+            tool_call_id: None,
+        }
+    }
+
+    fn session(messages: Vec<Message>) -> Session {
+        Session {
+            schema_version: SCHEMA_VERSION,
+            id: uuid::Uuid::new_v4().to_string(),
+            title: "Deploy notes".into(),
+            messages,
+            created_at: 100,
+            updated_at: 200,
+            ..Default::default()
+        }
+    }
+
+    fn texts(session: &Session) -> Vec<String> {
+        session.messages.iter().map(message_text).collect()
+    }
+
+    #[test]
+    fn import_validation_normalizes_or_rejects() {
+        let mut upper = session(vec![message("user", "hi")]);
+        upper.id = upper.id.to_uppercase();
+        upper.parent_id = Some("../../etc".into());
+        upper.forked_at = Some(3);
+        upper.title = "  ".into();
+        upper.summarized_through = 9;
+        upper.working_dir = Some("/".into());
+        upper.mode = Some(AgentMode::Build);
+        upper.enabled_skills = Some(vec!["shell".into()]);
+        validate_import(&mut upper).unwrap();
+        assert_eq!(upper.id, upper.id.to_lowercase());
+        assert_eq!((upper.parent_id, upper.forked_at), (None, None));
+        assert_eq!(upper.title, DEFAULT_TITLE);
+        assert_eq!(upper.summarized_through, 1);
+        assert_eq!(upper.working_dir, None);
+        assert!(upper.mode.is_none());
+        assert_eq!(upper.enabled_skills, None);
+
+        let mut bad_id = session(Vec::new());
+        bad_id.id = "../escape".into();
+        assert!(validate_import(&mut bad_id).is_err());
+
+        let mut bad_role = session(vec![message("root", "hi")]);
+        assert!(validate_import(&mut bad_role).is_err());
+    }
+
+    #[test]
+    fn import_conflicts_follow_the_chosen_strategy() {
+        use_test_store();
+        let original = session(vec![message("user", "deploy with password=hunter2"), message("assistant", "done")]);
+        assert_eq!(import_one(original.clone(), ImportConflict::Skip).unwrap().1, "imported");
+        assert_eq!(import_one(original.clone(), ImportConflict::Skip).unwrap(), (original.id.clone(), "skipped"));
+
+        let (renamed, status) = import_one(original.clone(), ImportConflict::Rename).unwrap();
+        assert_eq!(status, "renamed");
+        assert_ne!(renamed, original.id);
+
+        // An export of the same session, continued elsewhere: its copy of the history is redacted
+        let mut continued = original.clone();
+        continued.messages = redact_for_export(&original.messages, original.privacy);
+        continued.messages.push(message("user", "and roll back?"));
+        assert_eq!(import_one(continued, ImportConflict::Merge).unwrap(), (original.id.clone(), "merged"));
+        let merged = load_session(&original.id).unwrap();
+        assert_eq!(texts(&merged), ["deploy with password=hunter2", "done", "and roll back?"]);
+
+        let mut diverged = original.clone();
+        diverged.messages = vec![message("user", "something else")];
+        let (fork, status) = import_one(diverged, ImportConflict::Merge).unwrap();
+        assert_eq!(status, "forked");
+        assert_eq!(load_session(&fork).unwrap().parent_id.as_deref(), Some(original.id.as_str()));
+    }
+}
@@ -1,11 +1,19 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use std::sync::Mutex;
-use tauri::State;
+use tauri::{Manager, State};
 use keyring::Entry;
+use crate::schema::{self, Migration};
+
+const MIGRATIONS: &[Migration] = &[schema::unversioned];
+pub const SCHEMA_VERSION: u32 = schema::current_version(MIGRATIONS);
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct AppSettings {
+    #[serde(default)]
+    pub schema_version: u32,
     pub api_key: String, // deprecated single key
     #[serde(default)]
     pub openai_api_key: String,
@@ -20,11 +28,40 @@ pub struct AppSettings {
     pub reduced_motion: bool,
     #[serde(default)]
     pub high_contrast: bool,
+    // Audit log rotation: a segment is closed once it reaches this size (or at midnight UTC when daily)
+    #[serde(default = "default_audit_max_segment_mb")]
+    pub audit_max_segment_mb: u64,
+    #[serde(default)]
+    pub audit_rotate_daily: bool,
+    #[serde(default = "default_true")]
+    pub audit_compress_segments: bool,
+    // 0 keeps rotated segments forever
+    #[serde(default)]
+    pub audit_retention_days: u64,
+    // Mask secrets in tool results before they are sent to the model (logs and audit are always redacted)
+    #[serde(default)]
+    pub redact_tool_output: bool,
+    // OTLP/HTTP collector for agent traces, e.g. http://localhost:4318; empty disables export
+    #[serde(default)]
+    pub otlp_endpoint: String,
+    // Session logs roll over at this size; files untouched for `log_retention_days` are pruned at startup
+    #[serde(default = "default_log_max_file_mb")]
+    pub log_max_file_mb: u64,
+    #[serde(default = "default_log_retention_days")]
+    pub log_retention_days: u64,
+    // Unpinned sessions idle this long are deleted at startup; 0 keeps them forever
+    #[serde(default)]
+    pub session_retention_days: u64,
+    // Seal stored transcripts with a key from the OS keyring; message text is then left out of
+    // the search index, so search only covers titles and summaries
+    #[serde(default)]
+    pub encrypt_sessions: bool,
 }
 
 impl Default for AppSettings {
     fn default() -> Self {
         Self {
+            schema_version: SCHEMA_VERSION,
             api_key: "".to_string(),
             openai_api_key: "".to_string(),
             model: "gpt-3.5-turbo".to_string(),
@@ -33,6 +70,16 @@ impl Default for AppSettings {
             provider: default_provider(),
             reduced_motion: false,
             high_contrast: false,
+            audit_max_segment_mb: default_audit_max_segment_mb(),
+            audit_rotate_daily: false,
+            audit_compress_segments: true,
+            audit_retention_days: 0,
+            redact_tool_output: false,
+            otlp_endpoint: String::new(),
+            log_max_file_mb: default_log_max_file_mb(),
+            log_retention_days: default_log_retention_days(),
+            session_retention_days: 0,
+            encrypt_sessions: false,
         }
     }
 }
@@ -41,10 +88,30 @@ fn default_provider() -> String {
     "openai".into()
 }
 
+fn default_audit_max_segment_mb() -> u64 {
+    10
+}
+
+fn default_log_max_file_mb() -> u64 {
+    5
+}
+
+fn default_log_retention_days() -> u64 {
+    30
+}
+
+fn default_true() -> bool {
+    true
+}
+
 pub struct SettingsState(pub Mutex<AppSettings>);
 
-const SETTINGS_FILE: &str = "deskwork_settings.json";
-const KEYRING_SERVICE: &str = "deskwork";
+const SETTINGS_FILE: &str = "settings.json";
+// Written to the process CWD by earlier versions; moved into the config dir on first launch
+const LEGACY_SETTINGS_FILE: &str = "deskwork_settings.json";
+
+static SETTINGS_PATH: OnceLock<PathBuf> = OnceLock::new();
+pub const KEYRING_SERVICE: &str = "deskwork";
 const KEYRING_USER: &str = "openai_api_key";
 
 fn load_api_key_from_keyring() -> Option<String> {
@@ -66,7 +133,13 @@ fn save_api_key_to_keyring(api_key: &str) -> Result<(), String> {
 pub fn save_settings(settings: AppSettings, state: State<'_, SettingsState>) -> Result<(), String> {
     // 1. Update in-memory state
     let mut current_settings = state.0.lock().map_err(|e| e.to_string())?;
+    crate::vault::apply_settings(&settings)?;
+    let now_encrypting = settings.encrypt_sessions && !current_settings.encrypt_sessions;
     *current_settings = settings.clone();
+    crate::logging::apply_settings(&settings);
+    if now_encrypting {
+        crate::session_manager::clear_message_index()?;
+    }
 
     // 2. Persist secret to keyring, non-secrets to disk
     let key_to_store = if !settings.openai_api_key.is_empty() {
@@ -76,10 +149,13 @@ pub fn save_settings(settings: AppSettings, state: State<'_, SettingsState>) ->
     };
     save_api_key_to_keyring(&key_to_store)?;
     let mut disk_settings = settings.clone();
+    disk_settings.schema_version = SCHEMA_VERSION;
     disk_settings.api_key = "".into(); // legacy
     disk_settings.openai_api_key = "".into(); // Do not write secrets to disk
     let json = serde_json::to_string_pretty(&disk_settings).map_err(|e| e.to_string())?;
-    fs::write(SETTINGS_FILE, json).map_err(|e| e.to_string())?;
+    let path = SETTINGS_PATH.get().ok_or("Settings path not initialized")?;
+    schema::ensure_writable(path)?;
+    fs::write(path, json).map_err(|e| e.to_string())?;
 
     Ok(())
 }
@@ -95,13 +171,41 @@ pub fn get_settings(state: State<'_, SettingsState>) -> Result<AppSettings, Stri
     Ok(settings)
 }
 
+// Rename, falling back to copy + delete when the legacy file is on another volume.
+pub(crate) fn move_legacy_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    fs::remove_file(from)
+}
+
+// Resolves `<app_config_dir>/settings.json`, migrating the legacy CWD file, and loads it.
+pub fn init(app_handle: &tauri::AppHandle) -> SettingsState {
+    let config_dir = app_handle.path().app_config_dir().unwrap_or_default();
+    let path = config_dir.join(SETTINGS_FILE);
+    let legacy = Path::new(LEGACY_SETTINGS_FILE);
+    if !path.exists() && legacy.exists() {
+        if let Err(e) = move_legacy_file(legacy, &path) {
+            schema::warn(legacy, &format!("could not move it to {}: {}", path.display(), e));
+        }
+    }
+    let settings = load_initial_settings(if path.exists() { &path } else { legacy });
+    if let Err(e) = crate::vault::apply_settings(&settings) {
+        schema::warn(&path, &format!("{}; new session transcripts are stored unencrypted", e));
+    }
+    let _ = SETTINGS_PATH.set(path);
+    SettingsState(Mutex::new(settings))
+}
+
 // Helper to load from disk on startup
-pub fn load_initial_settings() -> AppSettings {
-    let mut settings = if let Ok(content) = fs::read_to_string(SETTINGS_FILE) {
-        serde_json::from_str(&content).unwrap_or_default()
-    } else {
-        AppSettings::default()
-    };
+fn load_initial_settings(path: &Path) -> AppSettings {
+    // An unreadable file is quarantined rather than overwritten by the next save
+    let mut settings: AppSettings =
+        schema::load_file(path, |value| schema::parse(value, MIGRATIONS)).unwrap_or_default();
 
     if let Some(api_key) = load_api_key_from_keyring() {
         settings.openai_api_key = api_key.clone();
@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 use std::sync::Mutex;
 use tauri::State;
 use std::collections::HashMap;
+use crate::session_manager::{update_session, SessionState};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Skill {
@@ -83,17 +84,43 @@ pub fn list_skills(state: State<'_, SkillState>) -> Result<Vec<Skill>, String> {
 }
 
 #[tauri::command]
-pub fn toggle_skill(state: State<'_, SkillState>, id: String, enabled: bool) -> Result<(), String> {
+pub fn toggle_skill(
+    state: State<'_, SkillState>,
+    session_state: State<'_, SessionState>,
+    id: String,
+    enabled: bool,
+) -> Result<(), String> {
     let mut skills = state.skills.lock().unwrap();
-    if let Some(skill) = skills.get_mut(&id) {
-        skill.enabled = enabled;
-        Ok(())
-    } else {
-        Err("Skill not found".to_string())
+    let Some(skill) = skills.get_mut(&id) else {
+        return Err("Skill not found".to_string());
+    };
+    skill.enabled = enabled;
+    // The current session keeps its own set of enabled skills
+    let enabled_ids: Vec<String> = skills.values().filter(|s| s.enabled).map(|s| s.id.clone()).collect();
+    drop(skills);
+    let current = session_state.current_session_id.lock().map_err(|e| e.to_string())?.clone();
+    if let Some(sid) = current {
+        let _ = update_session(&sid, |session| session.enabled_skills = Some(enabled_ids));
     }
+    Ok(())
 }
 
-pub fn is_tool_enabled(state: &State<'_, SkillState>, tool_name: &str) -> bool {
+// Sets the toggles to the skills a session is bound to; an unbound session gets the defaults
+// rather than whatever the previous session left switched on.
+pub fn apply_session_skills(state: &SkillState, session_skills: Option<&[String]>) -> Result<(), String> {
+    let defaults = SkillState::default().skills.into_inner().map_err(|e| e.to_string())?;
+    let mut skills = state.skills.lock().map_err(|e| e.to_string())?;
+    for skill in skills.values_mut() {
+        skill.enabled = match session_skills {
+            Some(ids) => ids.contains(&skill.id),
+            None => defaults.get(&skill.id).is_some_and(|default| default.enabled),
+        };
+    }
+    Ok(())
+}
+
+// `session_skills` (the ids a session is bound to) overrides the global toggles when set.
+pub fn is_tool_enabled(state: &State<'_, SkillState>, session_skills: Option<&[String]>, tool_name: &str) -> bool {
     let skills = state.skills.lock().unwrap();
     // Special internal tools always enabled
     if tool_name == "set_plan" || tool_name == "complete_step" {
@@ -102,9 +129,32 @@ pub fn is_tool_enabled(state: &State<'_, SkillState>, tool_name: &str) -> bool {
     
     for skill in skills.values() {
         if skill.tools.contains(&tool_name.to_string()) {
-            return skill.enabled;
+            return session_skills.map_or(skill.enabled, |ids| ids.contains(&skill.id));
         }
     }
     true // Default allow if not categorized (or deny? allow for now)
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled(state: &SkillState) -> Vec<String> {
+        let mut ids: Vec<String> = state.skills.lock().unwrap().values().filter(|s| s.enabled).map(|s| s.id.clone()).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn unbound_sessions_get_the_default_skills() {
+        let state = SkillState::default();
+        let defaults = enabled(&state);
+        apply_session_skills(&state, Some(&["file_system".to_string()])).unwrap();
+        assert_eq!(enabled(&state), ["file_system"]);
+
+        // The previous session's toggles do not carry over
+        apply_session_skills(&state, None).unwrap();
+        assert_eq!(enabled(&state), defaults);
+    }
+}
@@ -3,9 +3,16 @@ use std::fs;
 use std::path::PathBuf;
 use std::sync::Mutex;
 use tauri::{State, Manager};
+use serde_json::Value;
+use crate::schema::{self, Migration};
+
+const MIGRATIONS: &[Migration] = &[schema::unversioned];
+pub const SCHEMA_VERSION: u32 = schema::current_version(MIGRATIONS);
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Template {
+    #[serde(default)]
+    pub schema_version: u32,
     pub id: String,
     pub title: String,
     pub prompt: String,
@@ -25,17 +32,19 @@ impl TemplateState {
         let path = config_dir.join("templates.json");
         
         let templates = if path.exists() {
-            let content = fs::read_to_string(&path).unwrap_or_default();
-            serde_json::from_str(&content).unwrap_or_else(|_| Vec::new())
+            // A quarantined file leaves the list empty rather than re-seeding the defaults
+            schema::load_file(&path, parse_templates).unwrap_or_default()
         } else {
             // Default templates
             vec![
                 Template {
+                    schema_version: SCHEMA_VERSION,
                     id: uuid::Uuid::new_v4().to_string(),
                     title: "Code Review".to_string(),
                     prompt: "Please review the code in the current directory. Look for bugs, security issues, and performance improvements.".to_string(),
                 },
                 Template {
+                    schema_version: SCHEMA_VERSION,
                     id: uuid::Uuid::new_v4().to_string(),
                     title: "Summarize Project".to_string(),
                     prompt: "Read the README.md and the file structure, then summarize what this project does.".to_string(),
@@ -49,13 +58,22 @@ impl TemplateState {
         }
     }
 
-    fn save(&self) {
+    fn save(&self) -> Result<(), String> {
+        schema::ensure_writable(&self.path)?;
         let templates = self.templates.lock().unwrap();
         let content = serde_json::to_string_pretty(&*templates).unwrap_or_default();
         let _ = fs::write(&self.path, content);
+        Ok(())
     }
 }
 
+fn parse_templates(value: Value) -> Result<Vec<Template>, String> {
+    let Value::Array(records) = value else {
+        return Err("expected a list of templates".to_string());
+    };
+    records.into_iter().map(|record| schema::parse(record, MIGRATIONS)).collect()
+}
+
 #[tauri::command]
 pub fn list_templates(state: State<'_, TemplateState>) -> Result<Vec<Template>, String> {
     Ok(state.templates.lock().unwrap().clone())
@@ -64,12 +82,13 @@ pub fn list_templates(state: State<'_, TemplateState>) -> Result<Vec<Template>,
 #[tauri::command]
 pub fn save_template(state: State<'_, TemplateState>, title: String, prompt: String) -> Result<Template, String> {
     let template = Template {
+        schema_version: SCHEMA_VERSION,
         id: uuid::Uuid::new_v4().to_string(),
         title,
         prompt,
     };
     state.templates.lock().unwrap().push(template.clone());
-    state.save();
+    state.save()?;
     Ok(template)
 }
 
@@ -78,7 +97,6 @@ pub fn delete_template(state: State<'_, TemplateState>, id: String) -> Result<()
     let mut templates = state.templates.lock().unwrap();
     templates.retain(|t| t.id != id);
     drop(templates); // release lock before save
-    state.save();
-    Ok(())
+    state.save()
 }
 
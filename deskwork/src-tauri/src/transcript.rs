@@ -0,0 +1,368 @@
+use chrono::DateTime;
+use serde_json::Value;
+use std::collections::HashMap;
+use tauri::State;
+use crate::agent::{Message, MessageContent};
+use crate::audit::{self, AuditEntry, AuditState};
+use crate::session_manager::{self, Session};
+
+// Readable transcripts of one session (Markdown or a standalone HTML page) for attaching to
+// PRs and incident reports. The session's privacy level is applied first, as for JSON export.
+
+const TOOL_OUTPUT_MAX_CHARS: usize = 4_000;
+
+enum Block {
+    Message { role: String, text: String, images: Vec<String> },
+    Plan { steps: Vec<String> },
+    StepDone { index: u64 },
+    Tool { name: String, args: String, output: Option<String>, images: Vec<String> },
+}
+
+// Text and image URLs of a message; screenshots arrive either as parts or as data URLs inside
+// the text, which are moved out to the image list.
+fn split_content(content: &Option<MessageContent>) -> (String, Vec<String>) {
+    let (text, mut images) = match content {
+        Some(MessageContent::Text(text)) => (text.clone(), Vec::new()),
+        Some(MessageContent::Parts(parts)) => (
+            parts.iter().filter_map(|p| p.text.clone()).collect::<Vec<_>>().join("\n"),
+            parts.iter().filter_map(|p| p.image_url.as_ref().map(|i| i.url.clone())).collect(),
+        ),
+        None => (String::new(), Vec::new()),
+    };
+    let data_urls = crate::redact::data_url_regex();
+    images.extend(data_urls.find_iter(&text).map(|m| m.as_str().to_string()));
+    (data_urls.replace_all(&text, "").into_owned(), images)
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let head: String = text.chars().take(max_chars).collect();
+    format!("{}\n… (truncated)", head)
+}
+
+// Tool results are shown with the call that produced them; the system prompt is left out.
+fn blocks(messages: &[Message]) -> Vec<Block> {
+    let results: HashMap<&str, &Message> = messages
+        .iter()
+        .filter(|m| m.role == "tool")
+        .filter_map(|m| Some((m.tool_call_id.as_deref()?, m)))
+        .collect();
+    let mut out = Vec::new();
+    for message in messages {
+        if message.role == "system" || message.role == "tool" {
+            continue;
+        }
+        let (text, images) = split_content(&message.content);
+        if !text.trim().is_empty() || !images.is_empty() {
+            out.push(Block::Message { role: message.role.clone(), text, images });
+        }
+        for call in message.tool_calls.iter().flatten() {
+            let args: Value = serde_json::from_str(&call.function.arguments).unwrap_or(Value::Null);
+            match call.function.name.as_str() {
+                "set_plan" => out.push(Block::Plan {
+                    steps: args["steps"]
+                        .as_array()
+                        .map(|steps| steps.iter().filter_map(|s| s.as_str().map(String::from)).collect())
+                        .unwrap_or_default(),
+                }),
+                "complete_step" => out.push(Block::StepDone { index: args["step_index"].as_u64().unwrap_or(0) }),
+                name => {
+                    let (output, images) = match results.get(call.id.as_str()) {
+                        Some(result) => {
+                            let (text, images) = split_content(&result.content);
+                            (Some(truncate(&text, TOOL_OUTPUT_MAX_CHARS)), images)
+                        }
+                        None => (None, Vec::new()),
+                    };
+                    out.push(Block::Tool {
+                        name: name.to_string(),
+                        args: serde_json::to_string_pretty(&args).unwrap_or_else(|_| call.function.arguments.clone()),
+                        output,
+                        images,
+                    });
+                }
+            }
+        }
+    }
+    out
+}
+
+fn format_ts(secs: u64) -> String {
+    DateTime::from_timestamp(secs as i64, 0)
+        .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|| secs.to_string())
+}
+
+fn approval_label(entry: &AuditEntry) -> &str {
+    entry.approval.as_deref().unwrap_or("-")
+}
+
+// A fence longer than any backtick run in `text`, so tool output cannot close it early.
+fn fence(text: &str) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in text.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    "`".repeat(longest.max(2) + 1)
+}
+
+fn code_block(text: &str, lang: &str) -> String {
+    let fence = fence(text);
+    format!("{}{}\n{}\n{}\n\n", fence, lang, text, fence)
+}
+
+// Transcript text is shown literally: HTML in it would be live in the rendered page (and could
+// close the `<details>` blocks), and markdown in it would be reinterpreted.
+fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        let body = line.trim_start();
+        out.push_str(&line[..line.len() - body.len()]);
+        // List markers only count at the start of a line: `- x`, `+ x`, `1. x`
+        let digits = body.len() - body.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let marker = match body[digits..].chars().next() {
+            Some('.' | ')') if digits > 0 => Some(digits),
+            Some('-' | '+' | '=') if digits == 0 => Some(0),
+            _ => None,
+        };
+        for (pos, c) in body.char_indices() {
+            match c {
+                '&' => out.push_str("&amp;"),
+                '<' => out.push_str("&lt;"),
+                '>' => out.push_str("&gt;"),
+                '\\' | '`' | '*' | '_' | '[' | ']' | '#' | '|' | '~' | '!' => {
+                    out.push('\\');
+                    out.push(c);
+                }
+                _ if marker == Some(pos) => {
+                    out.push('\\');
+                    out.push(c);
+                }
+                _ => out.push(c),
+            }
+        }
+    }
+    out
+}
+
+// Markdown viewers do not show data URLs, and pasting the base64 would bloat the file; the
+// HTML transcript embeds them instead.
+fn markdown_images(images: &[String]) -> String {
+    images
+        .iter()
+        .map(|url| {
+            if url.starts_with("data:") {
+                "_[screenshot omitted; export as HTML to include it]_\n\n".to_string()
+            } else {
+                format!("![screenshot](<{}>)\n\n", url.replace('<', "%3C").replace('>', "%3E").replace(' ', "%20"))
+            }
+        })
+        .collect()
+}
+
+fn render_markdown(session: &Session, blocks: &[Block], actions: &[AuditEntry]) -> String {
+    let mut out = format!("# {}\n\n", escape_markdown(&session.title));
+    out.push_str(&format!("- Session: `{}`\n", session.id));
+    out.push_str(&format!("- Created: {}\n- Updated: {}\n", format_ts(session.created_at), format_ts(session.updated_at)));
+    if let Some(parent) = &session.parent_id {
+        out.push_str(&format!("- Forked from: `{}`\n", parent));
+    }
+    if !session.summary.is_empty() {
+        out.push_str(&format!("\n> {}\n", escape_markdown(&session.summary).replace('\n', "\n> ")));
+    }
+    out.push_str("\n## Conversation\n\n");
+    for block in blocks {
+        match block {
+            Block::Message { role, text, images } => {
+                out.push_str(&format!("### {}\n\n", if role == "user" { "User" } else { "Assistant" }));
+                if !text.trim().is_empty() {
+                    out.push_str(&format!("{}\n\n", escape_markdown(text.trim_end())));
+                }
+                out.push_str(&markdown_images(images));
+            }
+            Block::Plan { steps } => {
+                out.push_str("**Plan**\n\n");
+                for (i, step) in steps.iter().enumerate() {
+                    out.push_str(&format!("{}. {}\n", i + 1, escape_markdown(step).replace('\n', " ")));
+                }
+                out.push('\n');
+            }
+            Block::StepDone { index } => out.push_str(&format!("✓ Completed step {}\n\n", index.saturating_add(1))),
+            Block::Tool { name, args, output, images } => {
+                out.push_str(&format!("<details><summary>Tool: <code>{}</code></summary>\n\n", escape_html(name)));
+                out.push_str(&code_block(args, "json"));
+                match output {
+                    Some(text) if !text.trim().is_empty() => out.push_str(&code_block(text, "")),
+                    Some(_) => {}
+                    None => out.push_str("_No result recorded._\n\n"),
+                }
+                out.push_str(&markdown_images(images));
+                out.push_str("</details>\n\n");
+            }
+        }
+    }
+    if !actions.is_empty() {
+        out.push_str("## Actions and approvals\n\n| Time | Tool | Status | Authorized by | Action |\n|---|---|---|---|---|\n");
+        for entry in actions {
+            out.push_str(&format!(
+                "| {} | `{}` | {} | {} | {} |\n",
+                format_ts(entry.ts),
+                entry.tool.replace('`', ""),
+                escape_markdown(&entry.status),
+                escape_markdown(approval_label(entry)),
+                escape_markdown(&entry.action.replace('\n', " "))
+            ));
+        }
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+// Only inline images are embedded, which keeps the page self-contained and script-free.
+fn html_images(images: &[String]) -> String {
+    images
+        .iter()
+        .filter(|url| url.starts_with("data:image/"))
+        .map(|url| format!("<img src=\"{}\" alt=\"screenshot\">", escape_html(url)))
+        .collect()
+}
+
+const HTML_STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:900px;margin:2rem auto;padding:0 1rem;color:#1f2328}\
+.msg{border-radius:8px;padding:.75rem 1rem;margin:1rem 0;white-space:pre-wrap}\
+.user{background:#eef2ff}.assistant{background:#f6f8fa}\
+.role{font-weight:600;font-size:.8rem;text-transform:uppercase;color:#57606a;display:block;margin-bottom:.25rem}\
+details{border:1px solid #d0d7de;border-radius:8px;padding:.5rem 1rem;margin:.5rem 0}\
+pre{background:#f6f8fa;padding:.5rem;overflow-x:auto;white-space:pre-wrap}\
+img{max-width:100%;border:1px solid #d0d7de;margin:.5rem 0}\
+table{border-collapse:collapse;width:100%;font-size:.85rem}td,th{border:1px solid #d0d7de;padding:.25rem .5rem;text-align:left}\
+.meta{color:#57606a;font-size:.9rem}blockquote{border-left:4px solid #d0d7de;margin:1rem 0;padding-left:1rem;color:#57606a}";
+
+fn render_html(session: &Session, blocks: &[Block], actions: &[AuditEntry]) -> String {
+    let title = escape_html(&session.title);
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+        title, HTML_STYLE, title
+    );
+    out.push_str(&format!(
+        "<p class=\"meta\">Session <code>{}</code> · created {} · updated {}",
+        escape_html(&session.id),
+        format_ts(session.created_at),
+        format_ts(session.updated_at)
+    ));
+    if let Some(parent) = &session.parent_id {
+        out.push_str(&format!(" · forked from <code>{}</code>", escape_html(parent)));
+    }
+    out.push_str("</p>\n");
+    if !session.summary.is_empty() {
+        out.push_str(&format!("<blockquote>{}</blockquote>\n", escape_html(&session.summary)));
+    }
+    out.push_str("<h2>Conversation</h2>\n");
+    for block in blocks {
+        match block {
+            Block::Message { role, text, images } => {
+                let class = if role == "user" { "user" } else { "assistant" };
+                out.push_str(&format!(
+                    "<div class=\"msg {}\"><span class=\"role\">{}</span>{}{}</div>\n",
+                    class,
+                    class,
+                    escape_html(text.trim_end()),
+                    html_images(images)
+                ));
+            }
+            Block::Plan { steps } => {
+                out.push_str("<p><strong>Plan</strong></p>\n<ol>");
+                for step in steps {
+                    out.push_str(&format!("<li>{}</li>", escape_html(step)));
+                }
+                out.push_str("</ol>\n");
+            }
+            Block::StepDone { index } => out.push_str(&format!("<p>✓ Completed step {}</p>\n", index.saturating_add(1))),
+            Block::Tool { name, args, output, images } => {
+                out.push_str(&format!("<details><summary>Tool: <code>{}</code></summary>\n", escape_html(name)));
+                out.push_str(&format!("<pre>{}</pre>\n", escape_html(args)));
+                match output {
+                    Some(text) if !text.trim().is_empty() => out.push_str(&format!("<pre>{}</pre>\n", escape_html(text))),
+                    Some(_) => {}
+                    None => out.push_str("<p><em>No result recorded.</em></p>\n"),
+                }
+                out.push_str(&html_images(images));
+                out.push_str("</details>\n");
+            }
+        }
+    }
+    if !actions.is_empty() {
+        out.push_str("<h2>Actions and approvals</h2>\n<table>\n<tr><th>Time</th><th>Tool</th><th>Status</th><th>Authorized by</th><th>Action</th></tr>\n");
+        for entry in actions {
+            out.push_str(&format!(
+                "<tr><td>{}</td><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                format_ts(entry.ts),
+                escape_html(&entry.tool),
+                escape_html(&entry.status),
+                escape_html(approval_label(entry)),
+                escape_html(&entry.action)
+            ));
+        }
+        out.push_str("</table>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+// `format` is "markdown" (or "md") or "html"; the caller decides where to save the result.
+#[tauri::command]
+pub fn export_session(session_id: String, format: String, audit_state: State<'_, AuditState>) -> Result<String, String> {
+    let mut session = session_manager::load_session(&session_id)?;
+    session.messages = session_manager::redact_for_export(&session.messages, session.privacy);
+    let blocks = blocks(&session.messages);
+    // Audit actions are redacted when written; a missing audit log just drops the section
+    let actions = audit::session_entries(&audit_state, &session.id).unwrap_or_default();
+    match format.as_str() {
+        "markdown" | "md" => Ok(render_markdown(&session, &blocks, &actions)),
+        "html" => Ok(render_html(&session, &blocks, &actions)),
+        other => Err(format!("Unsupported export format '{}'. Use 'markdown' or 'html'.", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_text_is_shown_literally() {
+        assert_eq!(
+            escape_markdown("</details><script>x</script> **bold** [a](b)\n- item\n12. step"),
+            "&lt;/details&gt;&lt;script&gt;x&lt;/script&gt; \\*\\*bold\\*\\* \\[a\\](b)\n\\- item\n12\\. step"
+        );
+    }
+
+    #[test]
+    fn model_supplied_step_index_cannot_overflow() {
+        let blocks = [Block::StepDone { index: u64::MAX }];
+        let session = Session::default();
+        assert!(render_markdown(&session, &blocks, &[]).contains(&format!("Completed step {}", u64::MAX)));
+        assert!(render_html(&session, &blocks, &[]).contains(&format!("Completed step {}", u64::MAX)));
+    }
+
+    #[test]
+    fn inline_images_leave_the_markdown() {
+        let content = Some(MessageContent::Text("before data:image/png;base64,AAAA== after".into()));
+        let (text, images) = split_content(&content);
+        assert_eq!(text, "before  after");
+        assert_eq!(images, ["data:image/png;base64,AAAA=="]);
+        assert!(!markdown_images(&images).contains("base64"));
+    }
+}
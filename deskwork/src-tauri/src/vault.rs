@@ -0,0 +1,159 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::{engine::general_purpose, Engine as _};
+use keyring::Entry;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use crate::settings::{AppSettings, KEYRING_SERVICE};
+
+// Opt-in encryption at rest for stored session transcripts (tool calls, tool output, screenshots),
+// enabled by the `encrypt_sessions` setting. The AES-256-GCM key is generated on first use and
+// kept in the OS keyring next to the API key. Sealed transcripts stay readable after the setting
+// is turned off; only new writes go out in plaintext.
+
+const KEYRING_VAULT_KEY_USER: &str = "session_vault_key";
+const SEALED_PREFIX: &str = "vault:v1:";
+const NONCE_LEN: usize = 12;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+// Only a key that was loaded successfully is cached, so a locked keyring can be retried
+static VAULT_KEY: Mutex<Option<[u8; 32]>> = Mutex::new(None);
+
+// The key held by the keyring, None when there is none yet. Any other keyring error is returned
+// so the caller never mistakes an unreachable key for a missing one and replaces it.
+fn stored_key(stored: keyring::Result<String>) -> Result<Option<[u8; 32]>, String> {
+    match stored {
+        Ok(stored) => general_purpose::STANDARD
+            .decode(stored.trim())
+            .ok()
+            .and_then(|b| b.try_into().ok())
+            .map(Some)
+            .ok_or_else(|| "Stored session vault key is malformed".to_string()),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// `create` is only set when sealing; opening sealed data with no key cannot be helped by a new one.
+fn vault_key(create: bool) -> Result<[u8; 32], String> {
+    let mut cached = VAULT_KEY.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(key) = *cached {
+        return Ok(key);
+    }
+    let entry = Entry::new(KEYRING_SERVICE, KEYRING_VAULT_KEY_USER).map_err(|e| e.to_string())?;
+    let key = match stored_key(entry.get_password())? {
+        Some(key) => key,
+        None if create => {
+            let key = Aes256Gcm::generate_key(&mut OsRng);
+            entry
+                .set_password(&general_purpose::STANDARD.encode(key))
+                .map_err(|e| e.to_string())?;
+            key.into()
+        }
+        None => return Err("the session vault key is missing from the OS keyring".to_string()),
+    };
+    *cached = Some(key);
+    Ok(key)
+}
+
+// Called at startup and whenever settings are saved. Turning encryption on needs the keyring;
+// without one the error is returned and new transcripts keep being stored in plaintext.
+pub fn apply_settings(settings: &AppSettings) -> Result<(), String> {
+    if settings.encrypt_sessions {
+        if let Err(e) = vault_key(true) {
+            ENABLED.store(false, Ordering::Relaxed);
+            return Err(format!("Session encryption is unavailable (OS keyring not accessible): {}", e));
+        }
+    }
+    ENABLED.store(settings.encrypt_sessions, Ordering::Relaxed);
+    Ok(())
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+// Encrypts `plaintext` as `vault:v1:<base64 nonce||ciphertext>` when encryption is on,
+// otherwise returns it unchanged.
+pub fn seal(plaintext: &str) -> Result<String, String> {
+    if !enabled() {
+        return Ok(plaintext.to_string());
+    }
+    seal_with(&vault_key(true)?, plaintext)
+}
+
+fn seal_with(key: &[u8; 32], plaintext: &str) -> Result<String, String> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_bytes())
+        .map_err(|_| "Failed to encrypt session data".to_string())?;
+    let mut payload = nonce.to_vec();
+    payload.extend_from_slice(&ciphertext);
+    Ok(format!("{}{}", SEALED_PREFIX, general_purpose::STANDARD.encode(payload)))
+}
+
+// Reverses `seal`; unsealed input (written with encryption off) passes through.
+pub fn open(stored: &str) -> Result<String, String> {
+    let Some(encoded) = stored.strip_prefix(SEALED_PREFIX) else {
+        return Ok(stored.to_string());
+    };
+    let key = vault_key(false).map_err(|e| format!("Session is encrypted but the vault key is unavailable: {}", e))?;
+    open_with(&key, encoded)
+}
+
+fn open_with(key: &[u8; 32], encoded: &str) -> Result<String, String> {
+    let payload = general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|e| format!("Corrupt sealed session data: {}", e))?;
+    if payload.len() < NONCE_LEN {
+        return Err("Corrupt sealed session data: too short".to_string());
+    }
+    let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Failed to decrypt session data (wrong key or tampered data)".to_string())?;
+    String::from_utf8(plaintext).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 32] = [3u8; 32];
+
+    #[test]
+    fn sealed_text_round_trips() {
+        let sealed = seal_with(&KEY, "tool output: ok").unwrap();
+        assert!(sealed.starts_with(SEALED_PREFIX));
+        assert!(!sealed.contains("tool output"));
+        assert_eq!(open_with(&KEY, sealed.strip_prefix(SEALED_PREFIX).unwrap()).unwrap(), "tool output: ok");
+        // Unsealed records written with encryption off are read as they are
+        assert_eq!(open("[{\"role\":\"user\"}]").unwrap(), "[{\"role\":\"user\"}]");
+    }
+
+    #[test]
+    fn wrong_key_or_tampering_is_rejected() {
+        let sealed = seal_with(&KEY, "secret plan").unwrap();
+        let encoded = sealed.strip_prefix(SEALED_PREFIX).unwrap();
+        assert!(open_with(&[4u8; 32], encoded).is_err());
+
+        let mut payload = general_purpose::STANDARD.decode(encoded).unwrap();
+        let last = payload.len() - 1;
+        payload[last] ^= 1;
+        assert!(open_with(&KEY, &general_purpose::STANDARD.encode(payload)).is_err());
+    }
+
+    #[test]
+    fn only_a_missing_entry_allows_a_new_key() {
+        assert_eq!(stored_key(Err(keyring::Error::NoEntry)), Ok(None));
+        // A locked or unreachable keyring must not look like "no key yet", or the stored key
+        // would be overwritten and every sealed transcript lost
+        assert!(stored_key(Err(keyring::Error::NoStorageAccess("locked".into()))).is_err());
+        assert!(stored_key(Err(keyring::Error::PlatformFailure("dbus timeout".into()))).is_err());
+        let encoded = general_purpose::STANDARD.encode(KEY);
+        assert_eq!(stored_key(Ok(encoded)), Ok(Some(KEY)));
+        assert!(stored_key(Ok("not a key".into())).is_err());
+    }
+}
@@ -1,12 +1,12 @@
 import { useState, useEffect, useRef, useMemo } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
-import { open } from "@tauri-apps/plugin-dialog";
+import { open, save } from "@tauri-apps/plugin-dialog";
 import { 
   Send, Terminal, Settings as SettingsIcon, MessageSquare, Loader2, 
   CheckCircle2, FileText, FolderOpen, Plus, Folder, LayoutTemplate, 
   Globe, Cpu, Search, Monitor, Star, StarOff, Edit3, AlertOctagon, 
-  Copy, ChevronDown, ChevronRight, Play, Check, X, Bookmark, Shield, Hammer
+  Copy, ChevronDown, ChevronRight, Play, Check, X, Bookmark, Shield, Hammer, Archive, Trash2, Download
 } from "lucide-react";
 import ReactMarkdown from 'react-markdown';
 import remarkGfm from 'remark-gfm';
@@ -64,6 +64,10 @@ interface Session {
   updated_at: number;
   messages?: any[];
   pinned?: boolean;
+  summary?: string;
+  working_dir?: string;
+  mode?: "Plan" | "Build";
+  model?: string;
 }
 
 interface PendingApproval {
@@ -79,6 +83,13 @@ interface Template {
   prompt: string;
 }
 
+interface StorageWarning {
+  file: string;
+  quarantined_to?: string;
+  reason: string;
+  ts: number;
+}
+
 function App() {
   const [input, setInput] = useState("");
   const [messages, setMessages] = useState<Message[]>([]);
@@ -95,6 +106,8 @@ function App() {
   const [templates, setTemplates] = useState<Template[]>([]);
   const [showTemplates, setShowTemplates] = useState(false);
   const [agentMode, setAgentMode] = useState<"plan" | "build">("build");
+  // Model bound to the current session; empty follows the model in Settings
+  const [sessionModel, setSessionModel] = useState("");
 
   const messagesEndRef = useRef<HTMLDivElement>(null);
   const streamingMessageIndex = useRef<number | null>(null);
@@ -121,7 +134,9 @@ function App() {
 
   const filteredSessions = useMemo(() => {
     const q = sessionSearch.toLowerCase();
-    const list = q ? sessions.filter((s) => s.title.toLowerCase().includes(q) || s.id.toLowerCase().includes(q)) : sessions;
+    const list = q
+      ? sessions.filter((s) => s.title.toLowerCase().includes(q) || s.id.toLowerCase().includes(q) || (s.summary ?? "").toLowerCase().includes(q))
+      : sessions;
     return [...list].sort((a, b) => {
       const ap = a.pinned ? 1 : 0;
       const bp = b.pinned ? 1 : 0;
@@ -142,6 +157,22 @@ function App() {
       .catch((e) => console.error("Failed to load settings", e));
   }, []);
 
+  // Files that could not be read at startup were moved aside, or left in place if they could
+  // not be migrated; tell the user which and where they went
+  useEffect(() => {
+    invoke<StorageWarning[]>("get_storage_warnings")
+      .then((warnings) => {
+        if (warnings.length === 0) return;
+        setErrorToast({
+          message: `${warnings.length} data file${warnings.length === 1 ? "" : "s"} could not be read or migrated`,
+          detail: warnings
+            .map((w) => `${w.file}: ${w.reason}${w.quarantined_to ? ` (moved to ${w.quarantined_to})` : ""}`)
+            .join("\n"),
+        });
+      })
+      .catch((e) => console.error("Failed to load storage warnings", e));
+  }, []);
+
   useEffect(() => {
     const root = document.documentElement;
     root.classList.toggle("reduced-motion", displayPrefs.reduced_motion);
@@ -177,6 +208,17 @@ function App() {
     }
   }
 
+  async function saveSessionModel(model: string) {
+    if (!currentSessionId) return;
+    try {
+      const updated = await invoke<Session>("set_session_model", { session_id: currentSessionId, model: model.trim() || null });
+      setSessionModel(updated.model ?? "");
+      setSessions(prev => prev.map(s => (s.id === updated.id ? { ...s, model: updated.model } : s)));
+    } catch (e) {
+      console.error("Failed to set session model", e);
+    }
+  }
+
   async function saveAsTemplate() {
     const title = prompt("Template Title:");
     if (!title) return;
@@ -352,6 +394,11 @@ function App() {
       setPendingApprovals((prev) => prev.filter((p) => p.id !== payload.id));
     });
 
+    const unlistenSessionUpdated = listen<any>("session_updated", (event) => {
+      const payload = event.payload as any;
+      setSessions((prev) => prev.map((s) => (s.id === payload.id ? { ...s, title: payload.title, summary: payload.summary } : s)));
+    });
+
     const unlistenStream = listen<any>("chat_stream", (event) => {
       const payload = event.payload as any;
       if (payload.done) {
@@ -376,6 +423,7 @@ function App() {
       unlisten.then((f) => f());
       unlistenApprovalReq.then((f) => f());
       unlistenApprovalResolved.then((f) => f());
+      unlistenSessionUpdated.then((f) => f());
       unlistenStream.then((f) => f());
     };
   }, []);
@@ -412,6 +460,7 @@ function App() {
       const session = await invoke<Session>("create_session", { title: "New Chat" });
       setSessions(prev => [session, ...prev]);
       setCurrentSessionId(session.id);
+      setSessionModel(session.model ?? "");
       setMessages([]);
     } catch (e) {
       console.error("Failed create session", e);
@@ -423,6 +472,9 @@ function App() {
       const session: any = await invoke("switch_session", { session_id: id });
       setCurrentSessionId(session.id);
       setMessages(normalizeMessages(session.messages));
+      setSessionModel(session.model ?? "");
+      if (session.working_dir) setWorkingDir(session.working_dir);
+      loadAgentMode();
     } catch (e) {
       console.error("Failed switch session", e);
     }
@@ -430,7 +482,7 @@ function App() {
 
   async function handleRenameSession(id: string) {
     const title = prompt("New title");
-    if (!title) return;
+    if (!title?.trim()) return;
     try {
       const updated = await invoke<Session>("rename_session", { session_id: id, title });
       setSessions((prev) => prev.map((s) => (s.id === updated.id ? { ...s, title: updated.title } : s)));
@@ -449,6 +501,53 @@ function App() {
     }
   }
 
+  async function handleArchiveSession(id: string) {
+    try {
+      await invoke<Session>("archive_session", { session_id: id, archived: true });
+      setSessions((prev) => prev.filter((s) => s.id !== id));
+      if (currentSessionId === id) {
+        setCurrentSessionId(null);
+        setMessages([]);
+      }
+    } catch (e) {
+      console.error("Failed archive session", e);
+    }
+  }
+
+  async function handleDeleteSession(id: string) {
+    if (!confirm("Delete this session and its logs? This cannot be undone.")) return;
+    try {
+      await invoke("delete_session", { session_id: id });
+      setSessions((prev) => prev.filter((s) => s.id !== id));
+      if (currentSessionId === id) {
+        setCurrentSessionId(null);
+        setMessages([]);
+      }
+    } catch (e) {
+      console.error("Failed delete session", e);
+      setErrorToast({ message: "Delete failed", detail: String(e) });
+    }
+  }
+
+  async function handleExportTranscript(session: Session) {
+    try {
+      const path = await save({
+        defaultPath: `${session.title || "session"}.html`,
+        filters: [
+          { name: "HTML", extensions: ["html"] },
+          { name: "Markdown", extensions: ["md"] },
+        ],
+      });
+      if (!path) return;
+      const format = path.toLowerCase().endsWith(".md") ? "markdown" : "html";
+      const content = await invoke<string>("export_session", { session_id: session.id, format });
+      await invoke("write_file", { path, content });
+    } catch (e) {
+      console.error("Failed export transcript", e);
+      setErrorToast({ message: "Export failed", detail: String(e) });
+    }
+  }
+
   async function handleExportSessions() {
     try {
       const payload = await invoke<string>("export_sessions");
@@ -464,8 +563,14 @@ function App() {
     const payload = prompt("Paste exported sessions JSON");
     if (!payload) return;
     try {
-      const count = await invoke<number>("import_sessions", { payload });
-      alert(`Imported ${count} sessions`);
+      const results = await invoke<{ source_id: string; status: string; error?: string }[]>("import_sessions", { payload });
+      const stored = results.filter((r) => r.status !== "skipped" && r.status !== "failed").length;
+      const skipped = results.filter((r) => r.status === "skipped").length;
+      const failed = results.filter((r) => r.status === "failed");
+      alert(
+        `Imported ${stored} sessions, skipped ${skipped} already present` +
+          (failed.length ? `\nFailed: ${failed.map((r) => `${r.source_id} (${r.error})`).join(", ")}` : "")
+      );
       loadSessions();
     } catch (e) {
       console.error("Failed import sessions", e);
@@ -638,6 +743,27 @@ function App() {
               >
                 {session.pinned ? <Star className="w-4 h-4" /> : <StarOff className="w-4 h-4" />}
               </button>
+              <button
+                onClick={() => handleExportTranscript(session)}
+                className="text-zinc-500 hover:text-white"
+                title="Export transcript"
+              >
+                <Download className="w-4 h-4" />
+              </button>
+              <button
+                onClick={() => handleArchiveSession(session.id)}
+                className="text-zinc-500 hover:text-white"
+                title="Archive"
+              >
+                <Archive className="w-4 h-4" />
+              </button>
+              <button
+                onClick={() => handleDeleteSession(session.id)}
+                className="text-zinc-500 hover:text-red-400"
+                title="Delete"
+              >
+                <Trash2 className="w-4 h-4" />
+              </button>
             </div>
           ))}
         </div>
@@ -658,6 +784,17 @@ function App() {
         
         {/* Agent Mode Toggle (Top Right or Input) */}
         <div className="absolute top-4 right-4 z-50 flex bg-zinc-900 rounded-lg p-1 border border-white/5 shadow-xl">
+           {currentSessionId && (
+             <input
+               value={sessionModel}
+               onChange={(e) => setSessionModel(e.target.value)}
+               onBlur={(e) => saveSessionModel(e.target.value)}
+               onKeyDown={(e) => { if (e.key === "Enter") e.currentTarget.blur(); }}
+               placeholder="Default model"
+               title="Model for this session; leave empty to use the model from Settings"
+               className="w-32 mr-1 px-2 py-1.5 rounded-md bg-transparent text-xs text-zinc-300 placeholder-zinc-600 focus:outline-none focus:bg-white/5"
+             />
+           )}
            <button
              onClick={() => toggleAgentMode("plan")}
              className={`flex items-center gap-2 px-3 py-1.5 rounded-md text-xs font-medium transition-all ${
@@ -856,7 +993,7 @@ function App() {
               <AlertOctagon className="w-4 h-4 mt-0.5 shrink-0" />
               <div className="flex-1">
                 <div className="font-semibold">{errorToast.message}</div>
-                {errorToast.detail && <div className="text-xs text-red-100/80 break-words">{errorToast.detail}</div>}
+                {errorToast.detail && <div className="text-xs text-red-100/80 break-words whitespace-pre-line">{errorToast.detail}</div>}
               </div>
               <div className="flex gap-2">
                 <button
@@ -11,6 +11,12 @@ interface AppSettings {
   structured_logs?: boolean;
   reduced_motion?: boolean;
   high_contrast?: boolean;
+  audit_max_segment_mb?: number;
+  audit_rotate_daily?: boolean;
+  audit_compress_segments?: boolean;
+  audit_retention_days?: number;
+  session_retention_days?: number;
+  encrypt_sessions?: boolean;
 }
 
 interface AuditEntry {
@@ -32,10 +38,12 @@ export function SettingsModal({ isOpen, onClose }: SettingsModalProps) {
   const [model, setModel] = useState("gpt-4o");
   const [readOnly, setReadOnly] = useState(false);
   const [structuredLogs, setStructuredLogs] = useState(false);
+  const [encryptSessions, setEncryptSessions] = useState(false);
   const [provider, setProvider] = useState("openai");
   const [reducedMotion, setReducedMotion] = useState(false);
   const [highContrast, setHighContrast] = useState(false);
   const [loading, setLoading] = useState(false);
+  const [loadedSettings, setLoadedSettings] = useState<Partial<AppSettings>>({});
   const [auditLogs, setAuditLogs] = useState<AuditEntry[]>([]);
 
   useEffect(() => {
@@ -50,10 +58,12 @@ export function SettingsModal({ isOpen, onClose }: SettingsModalProps) {
   async function loadSettings() {
     try {
       const settings = await invoke<AppSettings>("get_settings");
+      setLoadedSettings(settings);
       setApiKey(settings.openai_api_key || settings.api_key);
       setModel(settings.model);
       setReadOnly(Boolean(settings.read_only));
       setStructuredLogs(Boolean(settings.structured_logs));
+      setEncryptSessions(Boolean(settings.encrypt_sessions));
       setProvider(settings.provider || "openai");
       setReducedMotion(Boolean(settings.reduced_motion));
       setHighContrast(Boolean(settings.high_contrast));
@@ -74,11 +84,11 @@ export function SettingsModal({ isOpen, onClose }: SettingsModalProps) {
   async function handleSave() {
     setLoading(true);
     try {
-      await invoke("save_settings", { settings: { api_key: apiKey, openai_api_key: apiKey, model, provider, read_only: readOnly, structured_logs: structuredLogs, reduced_motion: reducedMotion, high_contrast: highContrast } });
+      await invoke("save_settings", { settings: { ...loadedSettings, api_key: apiKey, openai_api_key: apiKey, model, provider, read_only: readOnly, structured_logs: structuredLogs, encrypt_sessions: encryptSessions, reduced_motion: reducedMotion, high_contrast: highContrast } });
       onClose();
     } catch (e) {
       console.error("Failed to save settings", e);
-      alert("Failed to save settings");
+      alert(`Failed to save settings: ${e}`);
     } finally {
       setLoading(false);
     }
@@ -217,6 +227,27 @@ export function SettingsModal({ isOpen, onClose }: SettingsModalProps) {
                 </div>
               </div>
 
+              {/* Session encryption toggle */}
+              <div className="space-y-2">
+                <div className="flex items-center justify-between px-3 py-3 rounded-xl border border-white/10 bg-black/30">
+                  <div>
+                    <div className="text-sm font-semibold text-white">Encrypt sessions</div>
+                    <div className="text-xs text-zinc-500">Seal stored transcripts with a key in the OS keyring. Search then covers titles and summaries only.</div>
+                  </div>
+                  <label className="inline-flex items-center cursor-pointer">
+                    <input
+                      type="checkbox"
+                      className="sr-only peer"
+                      checked={encryptSessions}
+                      onChange={(e) => setEncryptSessions(e.target.checked)}
+                    />
+                    <div className="w-11 h-6 bg-zinc-700 peer-focus:outline-none rounded-full peer peer-checked:bg-indigo-500 relative transition-colors">
+                      <div className={`absolute top-1 left-1 w-4 h-4 bg-white rounded-full transition-transform ${encryptSessions ? "translate-x-5" : ""}`}></div>
+                    </div>
+                  </label>
+                </div>
+              </div>
+
               {/* Accessibility toggles */}
               <div className="grid grid-cols-1 gap-2">
                 <div className="flex items-center justify-between px-3 py-3 rounded-xl border border-white/10 bg-black/30">